@@ -2,4 +2,9 @@
 
 pub mod prelude;
 
+pub mod key_pairs;
+pub mod players;
 pub mod profiles;
+pub mod reports;
+pub mod revocations;
+pub mod sessions;
@@ -1,3 +1,8 @@
 //! SeaORM Entity. Generated by sea-orm-codegen 0.9.2
 
+pub use super::key_pairs::Entity as KeyPairs;
+pub use super::players::Entity as Players;
 pub use super::profiles::Entity as Profiles;
+pub use super::reports::Entity as Reports;
+pub use super::revocations::Entity as Revocations;
+pub use super::sessions::Entity as Sessions;
@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use rsa::{PublicKeyParts, RsaPrivateKey};
+use rsa::pkcs1::EncodeRsaPrivateKey;
+use rsa::pkcs8::{DecodePrivateKey, EncodePrivateKey, LineEnding};
+use rsa::pkcs8::der::SecretDocument;
+use crate::CONFIG;
+use crate::model::SigningAlgorithm;
+
+/// A key pair used to sign and verify proxy server access tokens.
+pub struct SigningKey {
+    /// Key id put in the JWT header, which is the creation time of the key in milliseconds
+    pub kid: String,
+    pub algorithm: Algorithm,
+    pub encoding: EncodingKey,
+    pub decoding: DecodingKey,
+    created_at: i64,
+}
+
+/// All signing keys in the key directory, the newest one is used to sign new access tokens
+/// and the others are only kept to verify access tokens issued before rotating.
+pub struct Keyring {
+    keys: Vec<SigningKey>,
+}
+
+static KEYRING: Lazy<RwLock<Arc<Keyring>>> = Lazy::new(|| RwLock::new(Arc::new(Keyring { keys: vec![] })));
+
+impl Keyring {
+    /// The key to sign new access tokens
+    pub fn signing_key(&self) -> Option<&SigningKey> {
+        self.keys.last()
+    }
+
+    pub fn find(&self, kid: &str) -> Option<&SigningKey> {
+        self.keys.iter().find(|k| k.kid == kid)
+    }
+}
+
+fn key_path(dir: &Path, kid: &str) -> PathBuf {
+    dir.join(format!("{}.pem", kid))
+}
+
+/// Parse a PEM-encoded PKCS#8 private key, the algorithm is decided by the key type.
+fn parse_key(kid: &str, pem: &str) -> Result<SigningKey, String> {
+    let created_at = kid.parse::<i64>().map_err(|err| err.to_string())?;
+    let (_, document) = SecretDocument::from_pem(pem).map_err(|err| err.to_string())?;
+    let (algorithm, encoding, decoding) = if let Ok(key) = RsaPrivateKey::from_pkcs8_der(document.as_bytes()) {
+        let der = key.to_pkcs1_der().map_err(|err| err.to_string())?;
+        (Algorithm::RS256,
+         EncodingKey::from_rsa_der(der.as_bytes()),
+         DecodingKey::from_rsa_raw_components(&key.n().to_bytes_be(), &key.e().to_bytes_be()))
+    } else if let Ok(key) = Ed25519KeyPair::from_pkcs8_maybe_unchecked(document.as_bytes()) {
+        // Ed25519 keys are parsed by ring, as they may be encoded in PKCS#8 v2 with the public key
+        (Algorithm::EdDSA,
+         EncodingKey::from_ed_der(document.as_bytes()),
+         DecodingKey::from_ed_der(key.public_key().as_ref()))
+    } else {
+        return Err("Unsupported key, only RSA and Ed25519 keys are supported".to_string());
+    };
+    Ok(SigningKey { kid: kid.to_string(), algorithm, encoding, decoding, created_at })
+}
+
+/// Load all keys from the key directory, sorted by their creation time.
+///
+/// Files are named `{kid}.pem`, and those with other names are ignored.
+fn load(dir: &Path) -> Result<Keyring, String> {
+    let mut keys = vec![];
+    if !dir.exists() {
+        return Ok(Keyring { keys });
+    }
+    let entries = std::fs::read_dir(dir).map_err(|err| err.to_string())?;
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        let kid = match (path.file_stem().and_then(|s| s.to_str()), path.extension().and_then(|s| s.to_str())) {
+            (Some(kid), Some("pem")) if kid.chars().all(|c| c.is_ascii_digit()) => { kid.to_string() }
+            _ => {
+                warn!("Ignore unknown file in key directory: {}", path.display());
+                continue;
+            }
+        };
+        let pem = std::fs::read_to_string(&path).map_err(|err| err.to_string())?;
+        match parse_key(&kid, &pem) {
+            Ok(key) => { keys.push(key) }
+            Err(err) => { return Err(format!("Load key {} failed: {}", path.display(), err)); }
+        }
+    }
+    keys.sort_by_key(|k| k.created_at);
+    Ok(Keyring { keys })
+}
+
+/// Generate a new PEM-encoded PKCS#8 private key
+fn generate(algorithm: SigningAlgorithm) -> Result<String, String> {
+    match algorithm {
+        SigningAlgorithm::RS256 => {
+            let key = RsaPrivateKey::new(&mut rand::thread_rng(), 2048).map_err(|err| err.to_string())?;
+            let pem = key.to_pkcs8_pem(LineEnding::default()).map_err(|err| err.to_string())?;
+            Ok(pem.to_string())
+        }
+        SigningAlgorithm::EdDSA => {
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).map_err(|err| err.to_string())?;
+            let document = SecretDocument::try_from(pkcs8.as_ref()).map_err(|err| err.to_string())?;
+            let pem = document.to_pem("PRIVATE KEY", LineEnding::default()).map_err(|err| err.to_string())?;
+            Ok(pem.to_string())
+        }
+    }
+}
+
+/// Generate a new signing key in the key directory, and return its key id.
+fn create_key(dir: &Path) -> Result<String, String> {
+    std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    let kid = Utc::now().timestamp_millis().to_string();
+    let pem = generate(CONFIG.token_signing.algorithm)?;
+    std::fs::write(key_path(dir, &kid), pem).map_err(|err| err.to_string())?;
+    Ok(kid)
+}
+
+/// Load the keyring, and create the first signing key if there is none.
+pub fn init() -> Result<(), String> {
+    let dir = Path::new(&CONFIG.token_signing.key_dir);
+    let mut keyring = load(dir)?;
+    if keyring.keys.is_empty() {
+        info!("No token signing key found, create it...");
+        create_key(dir)?;
+        keyring = load(dir)?;
+    }
+    if let Some(key) = keyring.signing_key() {
+        if key.algorithm != CONFIG.token_signing.algorithm.into() {
+            warn!("Current token signing key {} is {:?}, rotate the key to use {:?}",
+                key.kid, key.algorithm, CONFIG.token_signing.algorithm);
+        }
+        info!("Token signing key: {} ({:?}), {} keys in total", key.kid, key.algorithm, keyring.keys.len());
+    }
+    *KEYRING.write().map_err(|err| err.to_string())? = Arc::new(keyring);
+    Ok(())
+}
+
+/// Reload the keyring, so that keys rotated by other processes take effect.
+pub fn reload() -> Result<(), String> {
+    let keyring = load(Path::new(&CONFIG.token_signing.key_dir))?;
+    if keyring.keys.is_empty() {
+        return Err("No token signing key found".to_string());
+    }
+    *KEYRING.write().map_err(|err| err.to_string())? = Arc::new(keyring);
+    Ok(())
+}
+
+pub fn current() -> Arc<Keyring> {
+    match KEYRING.read() {
+        Ok(keyring) => { keyring.clone() }
+        Err(err) => { err.into_inner().clone() }
+    }
+}
+
+/// Create a new signing key, and delete the keys which have been retired for longer than the token lifetime.
+///
+/// A key is retired when a newer key is created, and access tokens signed by it expire within the token lifetime.
+pub fn rotate() -> Result<(String, usize), String> {
+    let dir = Path::new(&CONFIG.token_signing.key_dir);
+    let kid = create_key(dir)?;
+    let deadline = (Utc::now() - Duration::seconds(CONFIG.token_lifetime.max_token_age())).timestamp_millis();
+    let pruned = prune(dir, deadline)?;
+    Ok((kid, pruned))
+}
+
+/// Delete the keys retired before the deadline in milliseconds, and return how many keys are deleted.
+fn prune(dir: &Path, deadline: i64) -> Result<usize, String> {
+    let keyring = load(dir)?;
+    let mut pruned = 0;
+    for (key, next) in keyring.keys.iter().zip(keyring.keys.iter().skip(1)) {
+        if next.created_at < deadline {
+            std::fs::remove_file(key_path(dir, &key.kid)).map_err(|err| err.to_string())?;
+            pruned += 1;
+        }
+    }
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("yggdrasil-proxy-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_key(dir: &Path, kid: &str, algorithm: SigningAlgorithm) {
+        std::fs::write(key_path(dir, kid), generate(algorithm).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn parse_generated_keys() {
+        let rsa = parse_key("1", &generate(SigningAlgorithm::RS256).unwrap()).unwrap();
+        assert_eq!(rsa.algorithm, Algorithm::RS256);
+        let ed = parse_key("2", &generate(SigningAlgorithm::EdDSA).unwrap()).unwrap();
+        assert_eq!(ed.algorithm, Algorithm::EdDSA);
+        assert!(parse_key("not-a-time", &generate(SigningAlgorithm::EdDSA).unwrap()).is_err());
+    }
+
+    #[test]
+    fn load_sorts_keys_and_ignores_unknown_files() {
+        let dir = temp_dir("keyring-load");
+        write_key(&dir, "3000", SigningAlgorithm::EdDSA);
+        write_key(&dir, "1000", SigningAlgorithm::EdDSA);
+        std::fs::write(dir.join("notes.txt"), "not a key").unwrap();
+        let keyring = load(&dir).unwrap();
+        let kids: Vec<&str> = keyring.keys.iter().map(|k| k.kid.as_str()).collect();
+        assert_eq!(kids, vec!["1000", "3000"]);
+        assert_eq!(keyring.signing_key().unwrap().kid, "3000");
+        assert!(keyring.find("1000").is_some());
+        assert!(keyring.find("2000").is_none());
+        assert!(load(&dir.join("missing")).unwrap().keys.is_empty());
+    }
+
+    #[test]
+    fn rotated_key_signs_and_retired_keys_still_verify() {
+        let dir = temp_dir("keyring-rotate");
+        write_key(&dir, "1000", SigningAlgorithm::EdDSA);
+        let old = load(&dir).unwrap();
+        let old_key = old.signing_key().unwrap();
+        let mut header = jsonwebtoken::Header::new(old_key.algorithm);
+        header.kid = Some(old_key.kid.clone());
+        let claims = serde_json::json!({ "exp": Utc::now().timestamp() + 60 });
+        let token = jsonwebtoken::encode(&header, &claims, &old_key.encoding).unwrap();
+        let kid = create_key(&dir).unwrap();
+        let keyring = load(&dir).unwrap();
+        assert_eq!(keyring.signing_key().unwrap().kid, kid);
+        let retired = keyring.find("1000").unwrap();
+        let validation = jsonwebtoken::Validation::new(retired.algorithm);
+        assert!(jsonwebtoken::decode::<serde_json::Value>(&token, &retired.decoding, &validation).is_ok());
+    }
+
+    #[test]
+    fn prune_keys_retired_before_deadline() {
+        let dir = temp_dir("keyring-prune");
+        write_key(&dir, "1000", SigningAlgorithm::EdDSA);
+        write_key(&dir, "2000", SigningAlgorithm::EdDSA);
+        write_key(&dir, "5000", SigningAlgorithm::EdDSA);
+        // key 1000 is retired at 2000, and key 2000 is retired at 5000
+        assert_eq!(prune(&dir, 3000).unwrap(), 1);
+        let kids: Vec<String> = load(&dir).unwrap().keys.into_iter().map(|k| k.kid).collect();
+        assert_eq!(kids, vec!["2000", "5000"]);
+        // the signing key is never pruned
+        assert_eq!(prune(&dir, i64::MAX).unwrap(), 1);
+        assert_eq!(load(&dir).unwrap().signing_key().unwrap().kid, "5000");
+    }
+}
@@ -1,3 +1,5 @@
+mod keyring;
+mod migration;
 mod model;
 mod proxy;
 mod entity;
@@ -10,15 +12,15 @@ use std::io::Read;
 use std::net::SocketAddr;
 use chrono::Local;
 use lazy_static::lazy_static;
-use log::{error, info};
+use log::{error, info, warn};
 use pretty_env_logger::env_logger;
-use rsa::pkcs8::{DecodePrivateKey, EncodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding};
 use rsa::{RsaPrivateKey, RsaPublicKey};
 use std::io::Write;
 use std::process::exit;
 
 use crate::model::{Config, Meta};
-use crate::web::{filters, handlers};
+use crate::web::{admin, filters, handlers};
 
 static IMPLEMENTATION_NAME: &str = "Yggdrasil API Reverse Proxy By MagicalSheep";
 static VERSION: &str = "0.1.0";
@@ -26,14 +28,53 @@ static VERSION: &str = "0.1.0";
 macro_rules! exit {
     ($err:expr) => {
         error!("{}", $err);
-        exit(0);
+        exit(1);
     };
 }
 
+#[cfg(not(test))]
 lazy_static! {
     static ref CONFIG: Config = load_config();
+}
+
+/// Configuration of unit tests, where backend server `a` is the main server in master slave mode,
+/// and `c` keeps the source names and UUIDs of profiles.
+#[cfg(test)]
+const TEST_CONFIG: &str = r#"
+meta:
+  skinDomains: []
+dataSource: "memory://"
+secret: unit-test-secret
+address: 127.0.0.1
+port: 8080
+backends:
+  - id: a
+    url: http://127.0.0.1:9001
+  - id: b
+    url: http://127.0.0.1:9002
+  - id: c
+    url: http://127.0.0.1:9003
+    naming:
+      template: "{name}"
+      mapping:
+        Herobrine: Notch
+    uuid: source
+main: a
+enableMasterSlaveMode: true
+tokenSigning:
+  algorithm: EdDSA
+  keyDir: target/test_token_keys
+"#;
+
+#[cfg(test)]
+lazy_static! {
+    static ref CONFIG: Config = serde_yaml::from_str(TEST_CONFIG).expect("Parse test configuration failed");
+}
+
+lazy_static! {
     static ref PRIVATE_KEY: RsaPrivateKey = load_private_key();
     static ref PUBLIC_KEY: String = load_public_key(); // just for meta display, so it is string type
+    static ref SERVICES_PUBLIC_KEYS: Vec<String> = load_services_public_keys();
 }
 
 fn pre_check() {
@@ -87,6 +128,7 @@ fn pre_check() {
     }
 }
 
+#[cfg(not(test))]
 fn load_config() -> Config {
     let mut file = match std::fs::File::open("config.yaml") {
         Ok(res) => { res }
@@ -98,11 +140,37 @@ fn load_config() -> Config {
         Ok(res) => { res }
         Err(err) => { exit!(err); }
     };
-    if config.backends.is_empty() {
+    if config.secret.is_empty() || config.secret == model::EXAMPLE_SECRET {
+        exit!("Please change the secret in configuration file to a random string");
+    }
+    let lifetime = &config.token_lifetime;
+    if lifetime.access_token <= 0 || lifetime.refresh_grace < 0 || lifetime.max_session_age <= 0 {
+        exit!("Token lifetime must be positive");
+    }
+    if config.health_check.interval == 0 || config.health_check.failure_threshold == 0 {
+        exit!("Health check interval and failure threshold must be positive");
+    }
+    if config.texture_proxy.enabled {
+        match reqwest::Url::parse(&config.texture_proxy.public_url) {
+            Ok(url) if url.host_str().is_some() => {}
+            _ => { exit!("Public url of texture proxy must be a valid url, such as https://auth.example.com"); }
+        }
+    }
+    if config.enabled_backends().next().is_none() {
         exit!("Backend server cannot be empty");
     }
-    if !config.backends.contains_key(&config.main) {
-        exit!("Main server name is not in backend server list");
+    for (i, backend) in config.backends.iter().enumerate() {
+        if config.backends[..i].iter().any(|b| b.id == backend.id) {
+            exit!(format!("Backend server <{}> is duplicated", backend.id));
+        }
+    }
+    match config.backend(&config.main) {
+        None => { exit!("Main server name is not in backend server list"); }
+        Some(main) if !main.enabled => { exit!("Main server cannot be disabled"); }
+        Some(_) => {}
+    }
+    for backend in &config.backends {
+        if let Err(err) = proxy::naming::check(&backend.id, &backend.options.naming) { exit!(err); }
     }
     config
 }
@@ -128,6 +196,29 @@ fn load_public_key() -> String {
     }
 }
 
+/// Base64-encoded DER of the current public key and the retired ones, in the format of Mojang services
+fn load_services_public_keys() -> Vec<String> {
+    let mut public_keys = vec![RsaPublicKey::from(&*PRIVATE_KEY)];
+    for path in &CONFIG.retired_public_keys {
+        let pem = match std::fs::read_to_string(path) {
+            Ok(res) => { res }
+            Err(err) => { exit!(format!("Read retired public key {} failed: {}", path, err)); }
+        };
+        match RsaPublicKey::from_public_key_pem(&pem) {
+            Ok(res) => { public_keys.push(res); }
+            Err(err) => { exit!(format!("Parse retired public key {} failed: {}", path, err)); }
+        }
+    }
+    let mut ret = vec![];
+    for public_key in public_keys {
+        match public_key.to_public_key_der() {
+            Ok(res) => { ret.push(base64::encode(res.as_ref())); }
+            Err(err) => { exit!(err); }
+        }
+    }
+    ret
+}
+
 fn init_log() {
     let env = env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info");
     env_logger::Builder::from_env(env)
@@ -145,15 +236,66 @@ fn init_log() {
         .init();
 }
 
+/// Reload token signing keys every minute, so that rotated keys take effect
+fn spawn_key_reloading() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            if let Err(err) = keyring::reload() {
+                warn!("Reload token signing keys failed: {}", err);
+            }
+        }
+    });
+}
+
+/// Prune expired records every hour
+fn spawn_pruning() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            if let Err(err) = utils::prune_expired().await {
+                warn!("Prune expired records failed: {:?}", err);
+            }
+        }
+    });
+}
+
 #[tokio::main]
 async fn main() {
     init_log();
     pre_check();
-    load_config();
+    lazy_static::initialize(&CONFIG);
     load_private_key();
     load_public_key();
+    lazy_static::initialize(&SERVICES_PUBLIC_KEYS);
+
+    match std::env::args().nth(1).as_deref() {
+        None => {}
+        Some("migrate") => {
+            if let Err(err) = repository::migrate(&CONFIG.data_source).await { exit!(err); }
+            info!("Migrate database successfully");
+            exit(0);
+        }
+        Some("rotate-key") => {
+            match keyring::rotate() {
+                Ok((kid, pruned)) => { info!("Create token signing key {}, and prune {} retired keys", kid, pruned); }
+                Err(err) => { exit!(err); }
+            }
+            exit(0);
+        }
+        Some(command) => { exit!(format!("Unknown command: {}", command)); }
+    }
 
+    if let Err(err) = proxy::client::init() { exit!(err); }
+    if let Err(err) = proxy::registry::init() { exit!(err); }
+    if let Err(err) = keyring::init() { exit!(err); }
     if let Err(err) = repository::init(&CONFIG.data_source).await { exit!(err); }
+    spawn_pruning();
+    spawn_key_reloading();
+    proxy::registry::spawn_probing();
+    proxy::backend_meta::spawn_refreshing();
 
     let log = warp::log::custom(|info| {
         info!(
@@ -172,8 +314,25 @@ async fn main() {
         .or(filters::has_join())
         .or(filters::profile())
         .or(filters::profiles())
+        .or(filters::profile_by_name())
         .or(filters::meta())
         .or(filters::certificates())
+        .or(filters::publickeys())
+        .or(filters::attributes())
+        .or(filters::update_attributes())
+        .or(filters::privileges())
+        .or(filters::blocklist())
+        .or(filters::report())
+        .or(filters::texture())
+        .or(filters::legacy_skin())
+        .or(admin::filters::sessions())
+        .or(admin::filters::kill_session())
+        .or(admin::filters::meta_cache())
+        .or(admin::filters::backends())
+        .or(admin::filters::player())
+        .or(admin::filters::update_player())
+        .or(admin::filters::reports())
+        .or(admin::filters::resolve_report())
         .with(log)
         .recover(handlers::err_handle);
 
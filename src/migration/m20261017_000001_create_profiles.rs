@@ -0,0 +1,56 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum Profiles {
+    Table,
+    Id,
+    BackendId,
+    SrcName,
+    SrcUuid,
+    Uuid,
+    Name,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // databases initialized before migrations were introduced already have this table
+        if manager.has_table("profiles").await? { return Ok(()); }
+        manager.create_table(
+            Table::create()
+                .table(Profiles::Table)
+                .col(ColumnDef::new(Profiles::Id).integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(Profiles::BackendId).string().not_null())
+                .col(ColumnDef::new(Profiles::SrcName).string().not_null())
+                .col(ColumnDef::new(Profiles::SrcUuid).string().not_null())
+                .col(ColumnDef::new(Profiles::Uuid).string().not_null().unique_key())
+                .col(ColumnDef::new(Profiles::Name).string().not_null().unique_key())
+                .to_owned()
+        ).await?;
+        manager.create_index(
+            Index::create()
+                .name("src_index")
+                .table(Profiles::Table)
+                .col(Profiles::BackendId)
+                .col(Profiles::SrcName)
+                .unique()
+                .to_owned()
+        ).await?;
+        manager.create_index(
+            Index::create()
+                .name("src_id_index")
+                .table(Profiles::Table)
+                .col(Profiles::BackendId)
+                .col(Profiles::SrcUuid)
+                .unique()
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(Profiles::Table).to_owned()).await
+    }
+}
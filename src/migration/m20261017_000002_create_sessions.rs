@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum Sessions {
+    Table,
+    Id,
+    Username,
+    Claims,
+    CreatedAt,
+    ExpiresAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(Sessions::Table)
+                .col(ColumnDef::new(Sessions::Id).string().not_null().primary_key())
+                .col(ColumnDef::new(Sessions::Username).string().not_null())
+                .col(ColumnDef::new(Sessions::Claims).text().not_null())
+                .col(ColumnDef::new(Sessions::CreatedAt).big_integer().not_null())
+                .col(ColumnDef::new(Sessions::ExpiresAt).big_integer().not_null())
+                .to_owned()
+        ).await?;
+        manager.create_index(
+            Index::create()
+                .name("session_username_index")
+                .table(Sessions::Table)
+                .col(Sessions::Username)
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(Sessions::Table).to_owned()).await
+    }
+}
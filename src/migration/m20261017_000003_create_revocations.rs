@@ -0,0 +1,37 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum Revocations {
+    Table,
+    Id,
+    RevokedAt,
+    ExpiresAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(Revocations::Table)
+                .col(ColumnDef::new(Revocations::Id).string().not_null().primary_key())
+                .col(ColumnDef::new(Revocations::RevokedAt).big_integer().not_null())
+                .col(ColumnDef::new(Revocations::ExpiresAt).big_integer().not_null())
+                .to_owned()
+        ).await?;
+        manager.create_index(
+            Index::create()
+                .name("revocation_expires_index")
+                .table(Revocations::Table)
+                .col(Revocations::ExpiresAt)
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(Revocations::Table).to_owned()).await
+    }
+}
@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum Players {
+    Table,
+    Uuid,
+    OnlineChat,
+    MultiplayerServer,
+    MultiplayerRealms,
+    ProfanityFilter,
+    BlockedProfiles,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(Players::Table)
+                .col(ColumnDef::new(Players::Uuid).string().not_null().primary_key())
+                .col(ColumnDef::new(Players::OnlineChat).boolean().not_null())
+                .col(ColumnDef::new(Players::MultiplayerServer).boolean().not_null())
+                .col(ColumnDef::new(Players::MultiplayerRealms).boolean().not_null())
+                .col(ColumnDef::new(Players::ProfanityFilter).boolean().not_null())
+                .col(ColumnDef::new(Players::BlockedProfiles).text().not_null())
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(Players::Table).to_owned()).await
+    }
+}
@@ -0,0 +1,34 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum KeyPairs {
+    Table,
+    Uuid,
+    PrivateKey,
+    PublicKey,
+    ExpiresAt,
+    RefreshedAfter,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(KeyPairs::Table)
+                .col(ColumnDef::new(KeyPairs::Uuid).string().not_null().primary_key())
+                .col(ColumnDef::new(KeyPairs::PrivateKey).text().not_null())
+                .col(ColumnDef::new(KeyPairs::PublicKey).text().not_null())
+                .col(ColumnDef::new(KeyPairs::ExpiresAt).big_integer().not_null())
+                .col(ColumnDef::new(KeyPairs::RefreshedAfter).big_integer().not_null())
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(KeyPairs::Table).to_owned()).await
+    }
+}
@@ -0,0 +1,40 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum Reports {
+    Table,
+    Id,
+    ReporterUuid,
+    ReportedUuid,
+    Reason,
+    Comments,
+    Evidence,
+    CreatedAt,
+    ResolvedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(Reports::Table)
+                .col(ColumnDef::new(Reports::Id).string().not_null().primary_key())
+                .col(ColumnDef::new(Reports::ReporterUuid).string().not_null())
+                .col(ColumnDef::new(Reports::ReportedUuid).string().not_null())
+                .col(ColumnDef::new(Reports::Reason).string().not_null())
+                .col(ColumnDef::new(Reports::Comments).text().not_null())
+                .col(ColumnDef::new(Reports::Evidence).text().not_null())
+                .col(ColumnDef::new(Reports::CreatedAt).big_integer().not_null())
+                .col(ColumnDef::new(Reports::ResolvedAt).big_integer())
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(Reports::Table).to_owned()).await
+    }
+}
@@ -0,0 +1,50 @@
+mod m20261017_000001_create_profiles;
+mod m20261017_000002_create_sessions;
+mod m20261017_000003_create_revocations;
+mod m20261017_000004_create_players;
+mod m20261017_000005_create_key_pairs;
+mod m20261017_000006_create_reports;
+
+use std::collections::HashSet;
+use log::info;
+use sea_orm::DatabaseConnection;
+use sea_orm_migration::prelude::*;
+
+pub struct Migrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for Migrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        vec![
+            Box::new(m20261017_000001_create_profiles::Migration),
+            Box::new(m20261017_000002_create_sessions::Migration),
+            Box::new(m20261017_000003_create_revocations::Migration),
+            Box::new(m20261017_000004_create_players::Migration),
+            Box::new(m20261017_000005_create_key_pairs::Migration),
+            Box::new(m20261017_000006_create_reports::Migration),
+        ]
+    }
+}
+
+/// Apply all pending migrations.
+///
+/// It refuses to touch the database if the schema has been upgraded by a newer proxy,
+/// as this binary knows nothing about those changes.
+pub async fn run(db: &DatabaseConnection) -> Result<(), DbErr> {
+    let known: HashSet<String> = Migrator::migrations().iter().map(|m| m.name().to_string()).collect();
+    let unknown: Vec<String> = Migrator::get_migration_models(db).await?
+        .into_iter()
+        .map(|m| m.version)
+        .filter(|version| !known.contains(version))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(DbErr::Migration(format!(
+            "Database schema is ahead of this proxy (unknown migrations: {}), please upgrade the proxy",
+            unknown.join(", "))));
+    }
+    Migrator::up(db, None).await?;
+    if let Some(latest) = Migrator::get_migration_models(db).await?.pop() {
+        info!("Database schema version: {}", latest.version);
+    }
+    Ok(())
+}
@@ -1,5 +1,7 @@
 use warp::http::StatusCode;
 
+/// Errors named after the exceptions of Yggdrasil API
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum CustomError {
     ForbiddenOperationException(StatusCode, String),
@@ -9,9 +11,9 @@ pub enum CustomError {
 
 impl warp::reject::Reject for CustomError {}
 
-impl Into<String> for &CustomError {
-    fn into(self) -> String {
-        match self {
+impl From<&CustomError> for String {
+    fn from(err: &CustomError) -> Self {
+        match err {
             CustomError::ForbiddenOperationException(_, _) => { "ForbiddenOperationException".to_string() }
             CustomError::IllegalArgumentException(_, _) => { "IllegalArgumentException".to_string() }
             CustomError::HttpException(_, msg) => { msg.clone() }
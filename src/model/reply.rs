@@ -1,4 +1,5 @@
-use crate::model::{KeyPair, Profile, User};
+use std::collections::HashMap;
+use crate::model::{KeyPair, ProfanityFilterPreferences, Profile, User};
 use serde_derive::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -48,4 +49,134 @@ pub struct ErrorReply {
     pub error_message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cause: Option<String>,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionReply {
+    pub id: String,
+    pub username: String,
+    pub profiles: Vec<String>,
+    #[serde(rename = "selectedProfile", skip_serializing_if = "Option::is_none")]
+    pub selected_profile: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetaCacheReply {
+    pub hits: u64,
+    pub misses: u64,
+    pub refreshes: u64,
+    pub failures: u64,
+    pub backends: HashMap<String, MetaCacheEntryReply>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetaCacheEntryReply {
+    #[serde(rename = "serverName", skip_serializing_if = "Option::is_none")]
+    pub server_name: Option<String>,
+    #[serde(rename = "fetchedAt")]
+    pub fetched_at: i64,
+    pub expired: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackendStatusReply {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub up: bool,
+    pub failures: u32,
+    #[serde(rename = "checkedAt", skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrivilegeReply {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrivilegesReply {
+    #[serde(rename = "onlineChat")]
+    pub online_chat: PrivilegeReply,
+    #[serde(rename = "multiplayerServer")]
+    pub multiplayer_server: PrivilegeReply,
+    #[serde(rename = "multiplayerRealms")]
+    pub multiplayer_realms: PrivilegeReply,
+    pub telemetry: PrivilegeReply,
+    #[serde(rename = "optionalTelemetry")]
+    pub optional_telemetry: PrivilegeReply,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlayerPrivilegesReply {
+    pub privileges: PrivilegesReply,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BanStatusReply {
+    #[serde(rename = "bannedScopes")]
+    pub banned_scopes: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttributesReply {
+    pub privileges: PrivilegesReply,
+    #[serde(rename = "profanityFilterPreferences")]
+    pub profanity_filter_preferences: ProfanityFilterPreferences,
+    #[serde(rename = "banStatus")]
+    pub ban_status: BanStatusReply,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlocklistReply {
+    #[serde(rename = "blockedProfiles")]
+    pub blocked_profiles: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlayerReply {
+    pub uuid: String,
+    #[serde(rename = "onlineChat")]
+    pub online_chat: bool,
+    #[serde(rename = "multiplayerServer")]
+    pub multiplayer_server: bool,
+    #[serde(rename = "multiplayerRealms")]
+    pub multiplayer_realms: bool,
+    #[serde(rename = "profanityFilter")]
+    pub profanity_filter: bool,
+    #[serde(rename = "blockedProfiles")]
+    pub blocked_profiles: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicKeyReply {
+    #[serde(rename = "publicKey")]
+    pub public_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicKeysReply {
+    #[serde(rename = "profilePropertyKeys")]
+    pub profile_property_keys: Vec<PublicKeyReply>,
+    #[serde(rename = "playerCertificateKeys")]
+    pub player_certificate_keys: Vec<PublicKeyReply>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportReply {
+    pub id: String,
+    pub reporter: String,
+    pub reported: String,
+    pub reason: String,
+    pub comments: String,
+    pub evidence: serde_json::Value,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "resolvedAt", skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<i64>,
+}
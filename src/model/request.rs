@@ -1,4 +1,4 @@
-use crate::model::Profile;
+use crate::model::{ProfanityFilterPreferences, Profile};
 use serde_derive::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,4 +65,49 @@ pub struct JoinQuery {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProfileQuery {
     pub unsigned: Option<bool>,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttributesRequest {
+    #[serde(rename = "profanityFilterPreferences")]
+    pub profanity_filter_preferences: Option<ProfanityFilterPreferences>,
+}
+
+/// Update of the player settings by admin, the absent fields are kept unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerRequest {
+    #[serde(rename = "onlineChat")]
+    pub online_chat: Option<bool>,
+    #[serde(rename = "multiplayerServer")]
+    pub multiplayer_server: Option<bool>,
+    #[serde(rename = "multiplayerRealms")]
+    pub multiplayer_realms: Option<bool>,
+    #[serde(rename = "profanityFilter")]
+    pub profanity_filter: Option<bool>,
+    #[serde(rename = "blockedProfiles")]
+    pub blocked_profiles: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportedEntity {
+    #[serde(rename = "profileId")]
+    pub profile_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportContent {
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(rename = "opinionComments", default)]
+    pub opinion_comments: Option<String>,
+    /// Signed chat messages attached by the client, which are kept as they are
+    #[serde(default)]
+    pub evidence: Option<serde_json::Value>,
+    #[serde(rename = "reportedEntity")]
+    pub reported_entity: ReportedEntity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportRequest {
+    pub report: ReportContent,
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use chrono::{DateTime, Duration, Utc};
+use log::{debug, info, warn};
+use once_cell::sync::Lazy;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::RsaPublicKey;
+use crate::CONFIG;
+use crate::model::Meta;
+use crate::model::reply::{MetaCacheEntryReply, MetaCacheReply};
+use crate::proxy::{registry, textures};
+
+/// Forced refreshing of a backend server is skipped if its metadata is younger than it,
+/// so that invalid signatures cannot make the proxy flood the backend server.
+const MIN_FORCED_REFRESH_INTERVAL: i64 = 30;
+/// How often the background task checks for expired metadata, in seconds
+const REFRESHING_INTERVAL: u64 = 60;
+
+/// Metadata of a backend server, with its signature public key parsed.
+pub struct BackendMeta {
+    pub meta: Meta,
+    pub public_key: RsaPublicKey,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl BackendMeta {
+    fn is_expired(&self, backend: &str) -> bool {
+        let ttl = CONFIG.options_of(backend).meta_cache_ttl;
+        Utc::now() - self.fetched_at >= Duration::seconds(ttl)
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    refreshes: AtomicU64,
+    failures: AtomicU64,
+}
+
+static CACHE: Lazy<RwLock<HashMap<String, Arc<BackendMeta>>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+static STATS: Lazy<Stats> = Lazy::new(Stats::default);
+
+fn cached(backend: &str) -> Option<Arc<BackendMeta>> {
+    match CACHE.read() {
+        Ok(cache) => { cache.get(backend).cloned() }
+        Err(err) => { err.into_inner().get(backend).cloned() }
+    }
+}
+
+async fn fetch(backend: &str) -> Result<BackendMeta, String> {
+    let backend = registry::get(backend).map_err(|err| String::from(&err))?;
+    let resp = backend.send(backend.get("")).await.map_err(|err| err.to_string())?;
+    let meta = resp.json::<Meta>().await.map_err(|err| err.to_string())?;
+    let public_key = RsaPublicKey::from_public_key_pem(&meta.signature_public_key).map_err(|err| err.to_string())?;
+    Ok(BackendMeta { meta, public_key, fetched_at: Utc::now() })
+}
+
+/// Whether the skin domain of a backend server can be published by the allow and deny lists
+fn is_allowed(domain: &str) -> bool {
+    let options = &CONFIG.auto_skin_domains;
+    (options.allow.is_empty() || options.allow.iter().any(|pattern| textures::matches_domain(pattern, domain)))
+        && !options.deny.iter().any(|pattern| textures::matches_domain(pattern, domain))
+}
+
+/// Log skin domains which are not in the previous metadata of the backend server
+fn log_new_skin_domains(backend: &str, previous: Option<&BackendMeta>, meta: &BackendMeta) {
+    for domain in &meta.meta.skin_domains {
+        if previous.is_some_and(|previous| previous.meta.skin_domains.contains(domain)) { continue; }
+        if is_allowed(domain) {
+            info!("Publish skin domain {} of <{}>", domain, backend);
+        } else {
+            warn!("Skin domain {} of <{}> is not allowed", domain, backend);
+        }
+    }
+}
+
+/// Fetch the metadata of the backend server and update the cache.
+async fn refresh(backend: &str) -> Option<Arc<BackendMeta>> {
+    STATS.refreshes.fetch_add(1, Ordering::Relaxed);
+    match fetch(backend).await {
+        Ok(meta) => {
+            debug!("Refresh metadata of <{}>", backend);
+            if CONFIG.auto_skin_domains.enabled {
+                log_new_skin_domains(backend, cached(backend).as_deref(), &meta);
+            }
+            let meta = Arc::new(meta);
+            match CACHE.write() {
+                Ok(mut cache) => { cache.insert(backend.to_string(), meta.clone()); }
+                Err(err) => { err.into_inner().insert(backend.to_string(), meta.clone()); }
+            }
+            Some(meta)
+        }
+        Err(err) => {
+            STATS.failures.fetch_add(1, Ordering::Relaxed);
+            warn!("Fetch metadata of <{}> failed: {}", backend, err);
+            None
+        }
+    }
+}
+
+/// Get the metadata of the backend server, it is fetched only if it has not been cached.
+///
+/// Expired metadata is still returned, as it is being refreshed in the background.
+pub async fn get(backend: &str) -> Option<Arc<BackendMeta>> {
+    if let Some(meta) = cached(backend) {
+        STATS.hits.fetch_add(1, Ordering::Relaxed);
+        return Some(meta);
+    }
+    STATS.misses.fetch_add(1, Ordering::Relaxed);
+    refresh(backend).await
+}
+
+/// Refresh the metadata of the backend server at once, which is used when its key may have been rotated.
+///
+/// Return `None` if the metadata has just been refreshed, or it cannot be fetched.
+pub async fn force_refresh(backend: &str) -> Option<Arc<BackendMeta>> {
+    if let Some(meta) = cached(backend) {
+        if Utc::now() - meta.fetched_at < Duration::seconds(MIN_FORCED_REFRESH_INTERVAL) {
+            return None;
+        }
+    }
+    refresh(backend).await
+}
+
+/// Refresh expired metadata of all backend servers in the background
+pub fn spawn_refreshing() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(REFRESHING_INTERVAL));
+        loop {
+            interval.tick().await;
+            for backend in CONFIG.enabled_backends() {
+                if cached(&backend.id).is_none_or(|meta| meta.is_expired(&backend.id)) {
+                    refresh(&backend.id).await;
+                }
+            }
+        }
+    });
+}
+
+/// Allowed skin domains in the cached metadata of all enabled backend servers
+pub fn skin_domains() -> Vec<String> {
+    let cache = match CACHE.read() {
+        Ok(cache) => { cache }
+        Err(err) => { err.into_inner() }
+    };
+    let mut skin_domains: Vec<String> = vec![];
+    for backend in CONFIG.enabled_backends() {
+        if let Some(meta) = cache.get(&backend.id) {
+            for domain in &meta.meta.skin_domains {
+                if is_allowed(domain) && !skin_domains.contains(domain) { skin_domains.push(domain.clone()); }
+            }
+        }
+    }
+    skin_domains
+}
+
+pub fn stats() -> MetaCacheReply {
+    let backends = match CACHE.read() {
+        Ok(cache) => { cache.clone() }
+        Err(err) => { err.into_inner().clone() }
+    };
+    MetaCacheReply {
+        hits: STATS.hits.load(Ordering::Relaxed),
+        misses: STATS.misses.load(Ordering::Relaxed),
+        refreshes: STATS.refreshes.load(Ordering::Relaxed),
+        failures: STATS.failures.load(Ordering::Relaxed),
+        backends: backends.into_iter().map(|(id, meta)| {
+            (id.clone(), MetaCacheEntryReply {
+                server_name: meta.meta.meta.server_name.clone(),
+                fetched_at: meta.fetched_at.timestamp(),
+                expired: meta.is_expired(&id),
+            })
+        }).collect(),
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+use once_cell::sync::OnceCell;
+use reqwest::{Certificate, Client, Proxy};
+use crate::{CONFIG, VERSION};
+
+/// How long idle connections are probed to keep them alive, in seconds
+const TCP_KEEPALIVE: u64 = 60;
+
+static SHARED: OnceCell<Client> = OnceCell::new();
+
+/// Split a PEM bundle into certificates, as each certificate has to be added separately.
+fn load_ca_bundle(path: &str) -> Result<Vec<Certificate>, String> {
+    let bundle = std::fs::read_to_string(path).map_err(|err| format!("Read CA bundle {} failed: {}", path, err))?;
+    let mut certificates = vec![];
+    for pem in bundle.split_inclusive("-----END CERTIFICATE-----") {
+        if !pem.contains("-----BEGIN CERTIFICATE-----") { continue; }
+        match Certificate::from_pem(pem.as_bytes()) {
+            Ok(certificate) => { certificates.push(certificate) }
+            Err(err) => { return Err(format!("Parse CA bundle {} failed: {}", path, err)); }
+        }
+    }
+    if certificates.is_empty() {
+        return Err(format!("No certificate found in CA bundle {}", path));
+    }
+    Ok(certificates)
+}
+
+/// Build a http client for backend servers by the `http` configuration.
+pub fn build(connect_timeout: u64) -> Result<Client, String> {
+    let options = &CONFIG.http;
+    let user_agent = match &options.user_agent {
+        Some(user_agent) => { user_agent.clone() }
+        None => { format!("yggdrasil-proxy/{}", VERSION) }
+    };
+    let mut builder = Client::builder()
+        .user_agent(user_agent)
+        .connect_timeout(Duration::from_secs(connect_timeout))
+        .pool_idle_timeout(Duration::from_secs(options.pool_idle_timeout))
+        .pool_max_idle_per_host(options.pool_max_idle_per_host)
+        .tcp_keepalive(Duration::from_secs(TCP_KEEPALIVE));
+    if !options.http2 {
+        builder = builder.http1_only();
+    }
+    if let Some(proxy) = &options.proxy {
+        let proxy = Proxy::all(proxy).map_err(|err| format!("Invalid proxy {}: {}", proxy, err))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &options.ca_bundle {
+        for certificate in load_ca_bundle(path)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    builder.build().map_err(|err| err.to_string())
+}
+
+/// Build the shared http client, so that configuration errors are found before serving.
+pub fn init() -> Result<(), String> {
+    let client = build(CONFIG.http.connect_timeout)?;
+    SHARED.set(client).map_err(|_| "Http client has been initialized".to_string())
+}
+
+/// The http client shared by all backend servers, cloning it shares the connection pool.
+pub fn shared() -> &'static Client {
+    SHARED.get().expect("Http client is not initialized")
+}
@@ -1,85 +1,120 @@
+#[allow(clippy::module_inception)]
 pub mod proxy;
 pub mod pre_proxy;
+pub mod naming;
+pub mod uuids;
+pub mod backend_meta;
+pub mod registry;
+pub mod client;
+pub mod textures;
+pub mod players;
 
-use base64::decode;
-use log::debug;
-use reqwest::{Client, StatusCode};
+use base64::{decode, encode};
+use chrono::Utc;
+use log::{debug, warn};
+use reqwest::StatusCode;
 use rsa::pkcs1v15::VerifyingKey;
-use rsa::pkcs8::DecodePublicKey;
 use rsa::RsaPublicKey;
 use sea_orm::ActiveValue;
 use sha1::Sha1;
 use signature::Verifier;
-use uuid::Uuid;
 use crate::CONFIG;
 use crate::model::errors::CustomError;
-use crate::model::{Meta, Profile, Property};
+use crate::model::{Profile, Property};
 use crate::repository::{find_by_backend_and_uuid, save_profile};
 use crate::utils::signature;
 
+fn verify(public_key: &RsaPublicKey, sign: &[u8], content: &str) -> bool {
+    let verifying_key = VerifyingKey::<Sha1>::new_with_prefix(public_key.clone());
+    let sign = rsa::pkcs1v15::Signature::from(sign.to_vec());
+    // content is already a base64 encoded str
+    verifying_key.verify(content.as_ref(), &sign).is_ok()
+}
+
 /// Validate signature from backend server
+///
+/// The public key of backend server is cached, and it will be refreshed at once if the validation fails,
+/// in case that the backend server has rotated its key.
 async fn validate_sign(src_backend: &str, sign: &str, content: &str) -> bool {
     // sign is a base64 encoded str, should decode it firstly
     let sign = match decode(sign) {
         Ok(res) => { res }
         Err(_) => { return false; }
     };
-    // get backend server rsa public key
-    let url = match CONFIG.backends.get(src_backend) {
-        None => { return false; }
-        Some(u) => { u }
-    };
-    let resp = match Client::new().get(url).send().await {
-        Ok(res) => { res }
-        Err(_) => { return false; }
-    };
-    let public_key_str = match resp.json::<Meta>().await {
-        Ok(res) => { res.signature_public_key }
-        Err(_) => { return false; }
-    };
-    // build public key
-    let public_key = match RsaPublicKey::from_public_key_pem(&public_key_str) {
-        Ok(res) => { res }
-        Err(_) => { return false; }
+    let mut ok = match backend_meta::get(src_backend).await {
+        Some(meta) => { verify(&meta.public_key, &sign, content) }
+        None => { false }
     };
-    // verify signature
-    let verifying_key = VerifyingKey::<Sha1>::new_with_prefix(public_key);
-    let sign = rsa::pkcs1v15::Signature::from(sign);
-    // content is already a base64 encoded str
-    match verifying_key.verify(content.as_ref(), &sign) {
-        Ok(_) => {
-            debug!("Validate signature successfully, content: {}", content);
-            true
+    if !ok {
+        if let Some(meta) = backend_meta::force_refresh(src_backend).await {
+            ok = verify(&meta.public_key, &sign, content);
         }
-        Err(_) => {
-            debug!("Validate signature failed, content: {}", content);
-            false
+    }
+    if ok {
+        debug!("Validate signature successfully, content: {}", content);
+    } else {
+        debug!("Validate signature failed, content: {}", content);
+    }
+    ok
+}
+
+/// Rewrite the textures payload.
+///
+/// - Profile UUID and name are rewritten into the proxy ones if they are given, and the timestamp is refreshed.
+/// - Texture urls are rewritten to the texture proxy if it is enabled, unless they are not in the skin domains
+///   of the backend server.
+///
+/// Return `None` if the value is not a valid textures payload.
+async fn rewrite_textures(src_backend: &str, value: &str, profile: Option<(&str, &str)>) -> Option<String> {
+    let raw = decode(value).ok()?;
+    let mut payload = serde_json::from_slice::<serde_json::Value>(&raw).ok()?;
+    let object = payload.as_object_mut()?;
+    if let Some((id, name)) = profile {
+        object.insert("timestamp".to_string(), Utc::now().timestamp_millis().into());
+        object.insert("profileId".to_string(), id.into());
+        object.insert("profileName".to_string(), name.into());
+    }
+    if CONFIG.texture_proxy.enabled {
+        if let Some(serde_json::Value::Object(items)) = object.get_mut("textures") {
+            for item in items.values_mut() {
+                if let Some(serde_json::Value::String(url)) = item.get_mut("url") {
+                    match textures::register(src_backend, url).await {
+                        Ok(proxy_url) => { *url = proxy_url; }
+                        Err(err) => { warn!("Rewrite texture url {} failed: {}", url, err); }
+                    }
+                }
+            }
         }
     }
+    Some(encode(payload.to_string()))
 }
 
 /// Validate and resign signature for properties.
 ///
 /// It will validate the signature from backend server before resign it.
-/// An invalid signature will be skipped to resign so that
+/// A property with an invalid signature is kept as it is, so that
 /// it can cause a validation fail in Minecraft client.
-async fn re_signature(src_backend: &str, properties: Option<Vec<Property>>) -> Option<Vec<Property>> {
-    if let None = properties { return None; }
-    let properties = properties.unwrap();
+///
+/// The textures property is rewritten before resigning, if the proxy profile UUID and name are given
+/// or the texture proxy is enabled. Unsigned properties and those with an invalid signature are never rewritten,
+/// as they cannot be trusted.
+async fn re_signature(src_backend: &str, properties: Option<Vec<Property>>, profile: Option<(&str, &str)>) -> Option<Vec<Property>> {
+    let properties = properties?;
     let mut ret = vec![];
     for property in properties {
         let p = match &property.signature {
             None => { property }
-            Some(sign) => {
-                // if validate failed, skip it
-                if !validate_sign(src_backend, &sign, &property.value).await { property } else {
-                    let resign = signature(property.value.clone());
-                    debug!("Resign signature for content {} is: {}", &property.value, &resign);
-                    Property {
-                        name: property.name.clone(),
-                        value: property.value.clone(),
-                        signature: Some(resign),
-                    }
+            Some(sign) if !validate_sign(src_backend, sign, &property.value).await => { property }
+            Some(_) => {
+                let value = if property.name == "textures" && (profile.is_some() || CONFIG.texture_proxy.enabled) {
+                    rewrite_textures(src_backend, &property.value, profile).await.unwrap_or_else(|| property.value.clone())
+                } else { property.value.clone() };
+                let resign = signature(value.clone());
+                debug!("Resign signature for content {} is: {}", &value, &resign);
+                Property {
+                    name: property.name.clone(),
+                    value,
+                    signature: Some(resign),
                 }
             }
         };
@@ -88,12 +123,21 @@ async fn re_signature(src_backend: &str, properties: Option<Vec<Property>>) -> O
     Some(ret)
 }
 
+/// Resign the properties of a translated profile, and rewrite its textures property if it is enabled.
+async fn translate_properties(src_backend: &str, profile: &Profile, properties: Option<Vec<Property>>) -> Option<Vec<Property>> {
+    let rewrite = CONFIG.options_of(src_backend).rewrite_textures;
+    re_signature(src_backend, properties, if rewrite { Some((&profile.id, &profile.name)) } else { None }).await
+}
+
 /// Translate the profile from a specific backend server into the profile that the proxy server controls.
 ///
-/// - Profile name will be renamed to `{backend_server_id}_{username}`.
-/// - Profile UUID will be replaced by a new version 4 UUID.
+/// - Profile name will be renamed by the naming options of the backend server,
+///   which is `{backend_server_id}_{username}` by default.
+/// - Profile UUID will be replaced by the one allocated by the UUID strategy of the backend server,
+///   which is a random version 4 UUID by default.
 /// - Profile properties will be resigned signature using the proxy server private key
-/// for all properties that the signature exists.
+///   for all properties that the signature exists.
+/// - Profile UUID and name in the textures property will be rewritten if it is enabled by the backend server options.
 ///
 /// However, if src_backend is the main server, this function will return its origin UUID and name value.
 /// Although its value in the database has been changed.
@@ -109,7 +153,7 @@ pub async fn translate(src_backend: &str, profile: Profile) -> Result<Profile, C
     let res = res.unwrap();
 
     // profile name in proxy server
-    let name = format!("{}_{}", src_backend, &profile.name);
+    let name = naming::allocate(src_backend, &profile.id, &profile.name).await?;
 
     // if the profile is already in the database, just return it
     // before return, update profile name record
@@ -120,31 +164,21 @@ pub async fn translate(src_backend: &str, profile: Profile) -> Result<Profile, C
         if let Err(err) = save_profile(active_model).await {
             return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err)));
         }
-        return Ok(Profile {
-            id: if is_need_to_trans { row.uuid } else { profile.id },
-            name: if is_need_to_trans { name } else { profile.name },
-            properties: re_signature(src_backend, profile.properties).await,
-        });
+        if !is_need_to_trans {
+            return Ok(Profile {
+                properties: re_signature(src_backend, profile.properties, None).await,
+                ..profile
+            });
+        }
+        let mut ret = Profile { id: row.uuid, name, properties: None };
+        ret.properties = translate_properties(src_backend, &ret, profile.properties).await;
+        return Ok(ret);
     }
 
-    // no record, to create one, and assign the proxy server UUID for it
-
-    // // check whether backend server UUID can be used directly or not,
-    // // as no the same UUID in proxy server database so far.
-    // let check = find_by_uuid(&profile.id).await;
-    // if let Err(err) = check {
-    //     return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err)));
-    // }
-    // let check = check.unwrap();
-    // let uuid = match check {
-    //     None => { profile.id.clone() } // use backend server UUID directly
-    //     Some(_) => { Uuid::new_v4().to_string().replace("-", "") } // generate a new UUID
-    // };
-
-    // no matter what type of creation it is, just using Version 4 UUID.
+    // no record, to create one, and assign the proxy server UUID for it.
     // if src_backend is a main server, then return its src_uuid.
     // and if it doesn't enable the main server prefix, return src_name as well.
-    let uuid = Uuid::new_v4().to_string().replace("-", "");
+    let uuid = uuids::allocate(src_backend, &profile.id, &name).await?;
 
     // insert a new profile record into database
     let record = crate::entity::profiles::ActiveModel {
@@ -159,9 +193,91 @@ pub async fn translate(src_backend: &str, profile: Profile) -> Result<Profile, C
         return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err)));
     }
 
-    Ok(Profile {
-        id: if is_need_to_trans { uuid } else { profile.id },
-        name: if is_need_to_trans { name } else { profile.name },
-        properties: re_signature(src_backend, profile.properties).await,
-    })
-}
\ No newline at end of file
+    if !is_need_to_trans {
+        return Ok(Profile {
+            properties: re_signature(src_backend, profile.properties, None).await,
+            ..profile
+        });
+    }
+    let mut ret = Profile { id: uuid, name, properties: None };
+    ret.properties = translate_properties(src_backend, &ret, profile.properties).await;
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::{find_by_backend_and_uuid, init_memory};
+    use super::*;
+
+    /// Profiles are kept in the memory store shared by all tests, so each test uses its own names.
+    fn profile(id: &str, name: &str) -> Profile {
+        Profile { id: id.to_string(), name: name.to_string(), properties: None }
+    }
+
+    #[tokio::test]
+    async fn translate_new_profile() {
+        init_memory();
+        let ret = translate("b", profile("00000000000000000000000000000b11", "TrNew")).await.unwrap();
+        assert_eq!(ret.name, "b_TrNew");
+        assert_ne!(ret.id, "00000000000000000000000000000b11");
+        assert!(ret.properties.is_none());
+        let row = find_by_backend_and_uuid("b", "00000000000000000000000000000b11").await.unwrap().unwrap();
+        assert_eq!(row.uuid, ret.id);
+        assert_eq!(row.name, ret.name);
+        assert_eq!(row.src_name, "TrNew");
+    }
+
+    #[tokio::test]
+    async fn translate_existing_profile() {
+        init_memory();
+        let first = translate("b", profile("00000000000000000000000000000b12", "TrOld")).await.unwrap();
+        let again = translate("b", profile("00000000000000000000000000000b12", "TrOld")).await.unwrap();
+        assert_eq!(again.id, first.id);
+        assert_eq!(again.name, first.name);
+        // renaming the source profile renames the proxy profile, while its UUID is kept
+        let renamed = translate("b", profile("00000000000000000000000000000b12", "TrRenamed")).await.unwrap();
+        assert_eq!(renamed.id, first.id);
+        assert_eq!(renamed.name, "b_TrRenamed");
+        let row = find_by_backend_and_uuid("b", "00000000000000000000000000000b12").await.unwrap().unwrap();
+        assert_eq!(row.src_name, "TrRenamed");
+    }
+
+    #[tokio::test]
+    async fn translate_main_profile_in_master_slave_mode() {
+        init_memory();
+        let ret = translate("a", profile("00000000000000000000000000000a11", "TrMain")).await.unwrap();
+        assert_eq!(ret.id, "00000000000000000000000000000a11");
+        assert_eq!(ret.name, "TrMain");
+        let row = find_by_backend_and_uuid("a", "00000000000000000000000000000a11").await.unwrap().unwrap();
+        assert_ne!(row.uuid, ret.id);
+        let again = translate("a", profile("00000000000000000000000000000a11", "TrMain")).await.unwrap();
+        assert_eq!(again.id, ret.id);
+        assert_eq!(again.name, ret.name);
+    }
+
+    #[tokio::test]
+    async fn translate_profile_with_taken_name() {
+        init_memory();
+        let main = translate("a", profile("00000000000000000000000000000a13", "TrTaken")).await.unwrap();
+        assert_eq!(main.name, "TrTaken");
+        // the name of main server profile is its source name in master slave mode
+        let ret = translate("c", profile("00000000000000000000000000000c13", "TrTaken")).await.unwrap();
+        assert_eq!(ret.name, "TrTaken2");
+        // a profile keeps its own name
+        let again = translate("c", profile("00000000000000000000000000000c13", "TrTaken")).await.unwrap();
+        assert_eq!(again.name, "TrTaken2");
+    }
+
+    #[tokio::test]
+    async fn translate_profile_with_taken_uuid() {
+        init_memory();
+        let ret = translate("c", profile("00000000000000000000000000000c15", "TrSource")).await.unwrap();
+        assert_eq!(ret.id, "00000000000000000000000000000c15");
+        // the source UUID of main server profile is returned in master slave mode
+        translate("a", profile("00000000000000000000000000000c16", "TrMainUuid")).await.unwrap();
+        let ret = translate("c", profile("00000000000000000000000000000c16", "TrSlaveUuid")).await.unwrap();
+        assert_ne!(ret.id, "00000000000000000000000000000c16");
+        let row = find_by_backend_and_uuid("c", "00000000000000000000000000000c16").await.unwrap().unwrap();
+        assert_eq!(row.uuid, ret.id);
+    }
+}
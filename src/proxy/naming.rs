@@ -0,0 +1,189 @@
+use reqwest::StatusCode;
+use crate::CONFIG;
+use crate::model::NamingOptions;
+use crate::model::errors::CustomError;
+use crate::repository::{find_by_backend_and_name, find_by_name};
+
+/// Maximum length of a Minecraft profile name
+const MAX_NAME_LENGTH: usize = 16;
+/// Minimum length kept for the source name part, when it is truncated to fit the template
+const MIN_SRC_NAME_LENGTH: usize = 3;
+/// How many numbered names will be tried when the rendered name has been taken
+const MAX_ATTEMPTS: usize = 100;
+
+fn is_valid_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn sanitize(s: &str) -> String {
+    s.chars().map(|c| if is_valid_char(c) { c } else { '_' }).collect()
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= MAX_NAME_LENGTH && name.chars().all(is_valid_char)
+}
+
+/// Split the template into the parts before and after `{name}`, with `{backend}` replaced.
+fn split_template(backend: &str, template: &str) -> Option<(String, String)> {
+    let template = template.replace("{backend}", &sanitize(backend));
+    let (prefix, suffix) = template.split_once("{name}")?;
+    Some((prefix.to_string(), suffix.to_string()))
+}
+
+/// Check the naming options of a backend server against the Minecraft name rules.
+pub fn check(backend: &str, options: &NamingOptions) -> Result<(), String> {
+    let (prefix, suffix) = match split_template(backend, &options.template) {
+        Some(res) => { res }
+        None => { return Err(format!("Naming template of <{}> must contain {{name}}", backend)); }
+    };
+    if !prefix.chars().chain(suffix.chars()).all(is_valid_char) {
+        return Err(format!("Naming template of <{}> can only contain letters, digits and underscores", backend));
+    }
+    if prefix.len() + suffix.len() > MAX_NAME_LENGTH - MIN_SRC_NAME_LENGTH {
+        return Err(format!("Naming template of <{}> is too long, at most {} characters besides {{name}}",
+                           backend, MAX_NAME_LENGTH - MIN_SRC_NAME_LENGTH));
+    }
+    for (src_name, name) in &options.mapping {
+        if !is_valid_name(name) {
+            return Err(format!("Mapped name {} of <{}> {} is not a valid Minecraft name", name, backend, src_name));
+        }
+    }
+    Ok(())
+}
+
+/// Render the proxy profile name for a source profile by the naming options of its backend server.
+///
+/// Characters not allowed in Minecraft names are replaced by `_`,
+/// and the source name is truncated so that the whole name fits in 16 characters.
+pub fn render(backend: &str, src_name: &str) -> String {
+    let options = &CONFIG.options_of(backend).naming;
+    if let Some(name) = options.mapping.get(src_name) {
+        return name.clone();
+    }
+    // the template has been checked when loading configuration
+    let (prefix, suffix) = split_template(backend, &options.template).unwrap_or_default();
+    let room = MAX_NAME_LENGTH.saturating_sub(prefix.len() + suffix.len());
+    let name: String = sanitize(src_name).chars().take(room).collect();
+    format!("{}{}{}", prefix, name, suffix)
+}
+
+/// Whether the name is taken by another profile, either stored with the name,
+/// or a main server profile which is returned with its source name in master slave mode.
+async fn is_taken(backend: &str, src_uuid: &str, name: &str) -> Result<bool, CustomError> {
+    if CONFIG.enable_master_slave_mode && backend != CONFIG.main {
+        match find_by_backend_and_name(&CONFIG.main, name).await {
+            Ok(None) => {}
+            Ok(Some(_)) => { return Ok(true); }
+            Err(err) => {
+                return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err)));
+            }
+        }
+    }
+    match find_by_name(name).await {
+        Ok(None) => { Ok(false) }
+        Ok(Some(row)) => { Ok(row.backend_id != backend || row.src_uuid != src_uuid) }
+        Err(err) => { Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))) }
+    }
+}
+
+/// Allocate the proxy profile name for a source profile.
+///
+/// If the rendered name has been taken by another profile, a number will be appended to it
+/// (truncating the name if necessary) until an available one is found.
+pub async fn allocate(backend: &str, src_uuid: &str, src_name: &str) -> Result<String, CustomError> {
+    let name = render(backend, src_name);
+    for n in 1..=MAX_ATTEMPTS {
+        let candidate = if n == 1 { name.clone() } else {
+            let n = n.to_string();
+            let mut candidate: String = name.chars().take(MAX_NAME_LENGTH - n.len()).collect();
+            candidate.push_str(&n);
+            candidate
+        };
+        if !is_taken(backend, src_uuid, &candidate).await? {
+            return Ok(candidate);
+        }
+    }
+    Err(CustomError::HttpException(StatusCode::CONFLICT, format!("No available name for profile {}", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use sea_orm::ActiveValue::{NotSet, Set};
+    use crate::entity::profiles;
+    use crate::repository::{init_memory, save_profile};
+    use super::*;
+
+    /// Profiles are kept in the memory store shared by all tests, so each test uses its own names.
+    async fn save(backend_id: &str, src_uuid: &str, src_name: &str, name: &str) {
+        let profile = profiles::ActiveModel {
+            id: NotSet,
+            backend_id: Set(backend_id.to_string()),
+            src_name: Set(src_name.to_string()),
+            src_uuid: Set(src_uuid.to_string()),
+            uuid: Set(format!("{}-{}", backend_id, src_uuid)),
+            name: Set(name.to_string()),
+        };
+        save_profile(profile).await.unwrap();
+    }
+
+    #[test]
+    fn valid_names() {
+        assert!(is_valid_name("Steve_2"));
+        assert!(is_valid_name("abcdefghijklmnop"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("abcdefghijklmnopq"));
+        assert!(!is_valid_name("Ste-ve"));
+    }
+
+    #[test]
+    fn check_naming_options() {
+        let options = |template: &str| NamingOptions { template: template.to_string(), mapping: HashMap::new() };
+        assert!(check("ls", &options("{backend}_{name}")).is_ok());
+        assert!(check("ls", &options("{backend}")).is_err());
+        assert!(check("ls", &options("{name}@{backend}")).is_err());
+        assert!(check("ls", &options("abcdefghijklm{name}")).is_ok());
+        assert!(check("ls", &options("abcdefghijklmn{name}")).is_err());
+        let mut mapped = options("{name}");
+        mapped.mapping.insert("Alice".to_string(), "Alice!".to_string());
+        assert!(check("ls", &mapped).is_err());
+    }
+
+    #[test]
+    fn render_names() {
+        assert_eq!(render("b", "Alice"), "b_Alice");
+        assert_eq!(render("b", "Al-ice"), "b_Al_ice");
+        assert_eq!(render("b", "abcdefghijklmnop"), "b_abcdefghijklmn");
+        assert_eq!(render("c", "Alice"), "Alice");
+        assert_eq!(render("c", "Herobrine"), "Notch");
+    }
+
+    #[tokio::test]
+    async fn allocate_rendered_name() {
+        init_memory();
+        assert_eq!(allocate("b", "nm-free", "Free").await.unwrap(), "b_Free");
+        save("c", "nm-own", "NmOwn", "NmOwn").await;
+        assert_eq!(allocate("c", "nm-own", "NmOwn").await.unwrap(), "NmOwn");
+    }
+
+    #[tokio::test]
+    async fn allocate_numbered_name_when_taken() {
+        init_memory();
+        save("b", "nm-taken", "NmTaken", "NmTaken").await;
+        assert_eq!(allocate("c", "nm-taken", "NmTaken").await.unwrap(), "NmTaken2");
+        save("c", "nm-taken", "NmTaken", "NmTaken2").await;
+        assert_eq!(allocate("c", "nm-taken3", "NmTaken").await.unwrap(), "NmTaken3");
+        // the name is truncated to fit the number
+        save("b", "nm-long", "NmLong", "NmLongNameTaken1").await;
+        assert_eq!(allocate("c", "nm-long", "NmLongNameTaken1").await.unwrap(), "NmLongNameTaken2");
+    }
+
+    #[tokio::test]
+    async fn main_source_names_are_taken_in_master_slave_mode() {
+        init_memory();
+        save("a", "nm-main", "NmMain", "a_NmMain").await;
+        assert_eq!(allocate("c", "nm-slave", "NmMain").await.unwrap(), "NmMain2");
+        // the main server itself renders names as usual
+        assert_eq!(allocate("a", "nm-main", "NmMain").await.unwrap(), "a_NmMain");
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use chrono::{Duration, Utc};
+use reqwest::StatusCode;
+use uuid::Uuid;
+use crate::entity::{key_pairs, players};
+use crate::model::{KeyPair, ProfanityFilterPreferences};
+use crate::model::errors::CustomError;
+use crate::model::reply::{AttributesReply, BanStatusReply, PlayerReply, PrivilegeReply, PrivilegesReply};
+use crate::repository::{find_key_pair, find_player, save_key_pair};
+use crate::utils::sign_bytes;
+
+/// Lifetime of chat signing key pairs, in hours
+const KEY_PAIR_LIFETIME: i64 = 48;
+/// Time after which the client should fetch a new chat signing key pair, in hours
+const KEY_PAIR_REFRESH: i64 = 36;
+
+/// Settings of a player who has never been configured: chat and multiplayer are allowed,
+/// while realms are not, since they are only served by Mojang.
+fn default_player(uuid: &str) -> players::Model {
+    players::Model {
+        uuid: uuid.to_string(),
+        online_chat: true,
+        multiplayer_server: true,
+        multiplayer_realms: false,
+        profanity_filter: false,
+        blocked_profiles: "[]".to_string(),
+    }
+}
+
+/// Find the settings of the player by proxy UUID, or the default settings if there are none.
+///
+/// A token without selected profile always gets the default settings.
+pub async fn find_or_default(uuid: Option<&str>) -> Result<players::Model, CustomError> {
+    let uuid = match uuid {
+        Some(res) => { res }
+        None => { return Ok(default_player("")); }
+    };
+    match find_player(uuid).await {
+        Ok(res) => { Ok(res.unwrap_or_else(|| default_player(uuid))) }
+        Err(err) => { Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))) }
+    }
+}
+
+/// Profiles blocked by the player, in simple UUID form
+pub fn blocked_profiles(player: &players::Model) -> Vec<String> {
+    serde_json::from_str(&player.blocked_profiles).unwrap_or_default()
+}
+
+/// Normalize the UUIDs of blocked profiles to simple form, and drop the duplicated ones.
+pub fn normalize_profiles(profiles: &[String]) -> Result<Vec<String>, CustomError> {
+    let mut ret: Vec<String> = vec![];
+    for profile in profiles {
+        let uuid = match Uuid::parse_str(profile) {
+            Ok(res) => { res.simple().to_string() }
+            Err(_) => {
+                return Err(CustomError::IllegalArgumentException(StatusCode::BAD_REQUEST, format!("Invalid profile UUID {}", profile)));
+            }
+        };
+        if !ret.contains(&uuid) {
+            ret.push(uuid);
+        }
+    }
+    Ok(ret)
+}
+
+pub fn privileges(player: &players::Model) -> PrivilegesReply {
+    PrivilegesReply {
+        online_chat: PrivilegeReply { enabled: player.online_chat },
+        multiplayer_server: PrivilegeReply { enabled: player.multiplayer_server },
+        multiplayer_realms: PrivilegeReply { enabled: player.multiplayer_realms },
+        telemetry: PrivilegeReply { enabled: false },
+        optional_telemetry: PrivilegeReply { enabled: false },
+    }
+}
+
+pub fn attributes(player: &players::Model) -> AttributesReply {
+    AttributesReply {
+        privileges: privileges(player),
+        profanity_filter_preferences: ProfanityFilterPreferences { profanity_filter_on: player.profanity_filter },
+        ban_status: BanStatusReply { banned_scopes: HashMap::new() },
+    }
+}
+
+pub fn player_reply(player: players::Model) -> PlayerReply {
+    PlayerReply {
+        blocked_profiles: blocked_profiles(&player),
+        uuid: player.uuid,
+        online_chat: player.online_chat,
+        multiplayer_server: player.multiplayer_server,
+        multiplayer_realms: player.multiplayer_realms,
+        profanity_filter: player.profanity_filter,
+    }
+}
+
+/// Chat signing key pair of the profile with the proxy UUID.
+///
+/// The key pair is kept until `refreshedAfter`, so that the client gets the same key pair
+/// and chat messages signed with it can still be verified afterwards.
+pub async fn key_pair(uuid: &str) -> Result<key_pairs::Model, CustomError> {
+    let now = Utc::now();
+    match find_key_pair(uuid).await {
+        Ok(Some(res)) if res.refreshed_after > now.timestamp_millis() => { return Ok(res); }
+        Ok(_) => {}
+        Err(err) => { return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))); }
+    }
+    // generating a 2048-bit RSA key takes a while, which must not block the async runtime
+    let key_pair = match tokio::task::spawn_blocking(KeyPair::new).await {
+        Ok(res) => { res? }
+        Err(err) => { return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))); }
+    };
+    let model = key_pairs::Model {
+        uuid: uuid.to_string(),
+        private_key: key_pair.private_key,
+        public_key: key_pair.public_key,
+        expires_at: (now + Duration::hours(KEY_PAIR_LIFETIME)).timestamp_millis(),
+        refreshed_after: (now + Duration::hours(KEY_PAIR_REFRESH)).timestamp_millis(),
+    };
+    if let Err(err) = save_key_pair(model.clone()).await {
+        return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err)));
+    }
+    Ok(model)
+}
+
+/// Signature of the public key bound to the profile, as `publicKeySignatureV2`,
+/// which signs the profile UUID, the expiry time in milliseconds and the DER of the public key.
+pub fn public_key_signature_v2(uuid: &str, expires_at: i64, key_pair: &KeyPair) -> Result<String, CustomError> {
+    let uuid = match Uuid::parse_str(uuid) {
+        Ok(res) => { res }
+        Err(err) => { return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))); }
+    };
+    let mut content = uuid.as_bytes().to_vec();
+    content.extend_from_slice(&expires_at.to_be_bytes());
+    content.extend_from_slice(&key_pair.public_key_der()?);
+    Ok(sign_bytes(&content))
+}
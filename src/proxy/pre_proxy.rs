@@ -1,19 +1,94 @@
 use std::collections::HashMap;
 use futures::future::join_all;
+use log::debug;
 use warp::hyper::StatusCode;
 use crate::CONFIG;
-use crate::model::{AccessClaims, Profile, Property};
+use crate::entity::profiles;
+use crate::model::{AccessClaims, FanOut, LoginMode, Profile, Property};
 use crate::model::errors::CustomError;
 use crate::model::request::{JoinQuery, JoinRequest, ProfileQuery, RefreshRequest, ValidateRequest};
-use crate::repository::{find_by_name, find_by_src_name, find_by_uuid};
-use crate::utils::decode_token;
+use crate::proxy::registry::{self, Backend};
+use crate::repository::{find_by_backend_and_name, find_by_name, find_by_uuid};
+use crate::utils::{check_client_token, decode_refreshable_token, decode_token};
+
+/// Find the backend server hinted by the username, which is `{username}@{backend}` or `{backend}:{username}`,
+/// and return it with the username without the hint.
+fn login_hint(username: &str) -> Option<(String, String)> {
+    let find = |id: &str| CONFIG.backends.iter().find(|b| b.id.eq_ignore_ascii_case(id)).map(|b| b.id.clone());
+    if let Some((name, id)) = username.rsplit_once('@') {
+        if let Some(id) = find(id) { return Some((id, name.to_string())); }
+    }
+    if let Some((id, name)) = username.split_once(':') {
+        if let Some(id) = find(id) { return Some((id, name.to_string())); }
+    }
+    None
+}
+
+/// Backend servers whose email domains contain the domain of the username, except those never accepting logins
+fn email_backends(username: &str) -> Vec<String> {
+    let domain = match username.rsplit_once('@') {
+        Some((_, domain)) => { domain }
+        None => { return vec![]; }
+    };
+    CONFIG.backends.iter().filter(|backend| {
+        let options = &backend.options;
+        options.fan_out != FanOut::Never && options.email_domains.iter().any(|d| d.eq_ignore_ascii_case(domain))
+    }).map(|backend| backend.id.clone()).collect()
+}
+
+/// Decide which backend servers the credentials are sent to, and return them with the real username.
+///
+/// Credentials are only sent to the backend server hinted by the username, or those matched by the email domain,
+/// and they are sent to available backend servers whose fan-out policy is `always` only if neither matches.
+///
+/// Backend servers are sorted by their priority and then the configured order in sequential login mode,
+/// which is the order of trying them.
+pub fn login_pre_proxy(username: &str) -> Result<(String, Vec<&'static Backend>), CustomError> {
+    let (username, mut backends) = login_backends(username)?;
+    if CONFIG.login_mode == LoginMode::Sequential {
+        backends.sort_by_key(|backend| CONFIG.options_of(&backend.id).priority);
+    }
+    Ok((username, backends))
+}
+
+fn login_backends(username: &str) -> Result<(String, Vec<&'static Backend>), CustomError> {
+    if let Some((dst, username)) = login_hint(username) {
+        if CONFIG.options_of(&dst).fan_out == FanOut::Never {
+            return Err(CustomError::IllegalArgumentException(StatusCode::BAD_REQUEST,
+                                                             format!("Backend server <{}> does not accept logins", dst)));
+        }
+        debug!("Login of {} is routed to <{}> by hint", username, dst);
+        return Ok((username, vec![registry::get(&dst)?]));
+    }
+    let dsts = email_backends(username);
+    if dsts.is_empty() {
+        let backends = registry::available().into_iter()
+            .filter(|backend| CONFIG.options_of(&backend.id).fan_out == FanOut::Always)
+            .collect();
+        return Ok((username.to_string(), backends));
+    }
+    debug!("Login of {} is routed to {:?} by email domain", username, dsts);
+    let mut backends = vec![];
+    let mut unavailable = None;
+    for dst in dsts {
+        match registry::get(&dst) {
+            Ok(backend) => { backends.push(backend) }
+            Err(err) => { unavailable = Some(err) }
+        }
+    }
+    match unavailable {
+        Some(err) if backends.is_empty() => { Err(err) }
+        _ => { Ok((username.to_string(), backends)) }
+    }
+}
 
 pub async fn refresh_pre_proxy(request: RefreshRequest) -> Result<(String, AccessClaims, RefreshRequest), CustomError> {
-    // check token
-    let access_claims = match decode_token(&request.access_token) {
+    // check token, temporarily invalid token can be refreshed
+    let access_claims = match decode_refreshable_token(&request.access_token).await {
         Ok(val) => { val }
         Err(err) => { return Err(err); }
     };
+    check_client_token(&access_claims, &request.client_token)?;
 
     let mut properties: Option<Vec<Property>> = None; // raw properties
 
@@ -21,14 +96,14 @@ pub async fn refresh_pre_proxy(request: RefreshRequest) -> Result<(String, Acces
     let selected_uuid = match request.selected_profile.as_ref() {
         None => {
             // selected_uuid should not be none
-            if None == access_claims.selected_uuid {
+            if access_claims.selected_uuid.is_none() {
                 return Err(CustomError::ForbiddenOperationException(StatusCode::FORBIDDEN, "Invalid token.".to_string()));
             }
             access_claims.selected_uuid.as_ref().unwrap().clone()
         }
         Some(profile) => {
             // selected_uuid should be none
-            if None != access_claims.selected_uuid {
+            if access_claims.selected_uuid.is_some() {
                 return Err(CustomError::IllegalArgumentException(StatusCode::BAD_REQUEST, "Access token already has a profile assigned.".to_string()));
             }
             properties = profile.properties.clone();
@@ -36,15 +111,14 @@ pub async fn refresh_pre_proxy(request: RefreshRequest) -> Result<(String, Acces
         }
     };
 
-    let dst; // destination backend server id, it always has an valid value
-
-    match access_claims.uuids.get(&selected_uuid) {
+    // destination backend server id, it always has an valid value
+    let dst = match access_claims.uuids.get(&selected_uuid) {
         None => {
             // happen when receiving an invalid profile uuid
             return Err(CustomError::IllegalArgumentException(StatusCode::BAD_REQUEST, "Invalid uuid.".to_string()));
         }
-        Some(id) => { dst = id.clone() }
-    }
+        Some(id) => { id.clone() }
+    };
 
     // build request to backend server
     let profile = if !CONFIG.enable_master_slave_mode || CONFIG.main.ne(&dst) {
@@ -70,7 +144,7 @@ pub async fn refresh_pre_proxy(request: RefreshRequest) -> Result<(String, Acces
     } else { request.selected_profile };
 
     let access_token = access_claims.tokens.get(&dst).unwrap().clone();
-    let is_selected = access_claims.selected.get(&dst).unwrap().clone();
+    let is_selected = *access_claims.selected.get(&dst).unwrap();
     Ok((dst, access_claims, RefreshRequest {
         access_token,
         client_token: request.client_token,
@@ -80,11 +154,7 @@ pub async fn refresh_pre_proxy(request: RefreshRequest) -> Result<(String, Acces
 }
 
 pub async fn join_pre_proxy(request: JoinRequest) -> Result<(String, JoinRequest), CustomError> {
-    let access_claim;
-    match decode_token(&request.access_token) {
-        Ok(claim) => { access_claim = claim }
-        Err(err) => { return Err(err); }
-    }
+    let access_claim = decode_token(&request.access_token).await?;
     if let Some(selected_uuid) = access_claim.selected_uuid {
         if selected_uuid.ne(&request.selected_profile) {
             return Err(CustomError::ForbiddenOperationException(StatusCode::FORBIDDEN, "Invalid token.".to_string()));
@@ -124,7 +194,7 @@ pub async fn has_join_pre_proxy(query: JoinQuery) -> Result<(String, Vec<(String
     // if enable master slave mode, then only response the main server player join requests
     // if there is a same name profile from other auth servers.
     if CONFIG.enable_master_slave_mode {
-        let src = match find_by_src_name(&query.username).await {
+        let src = match find_by_backend_and_name(&CONFIG.main, &query.username).await {
             Ok(res) => { res }
             Err(err) => { return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))); }
         };
@@ -161,6 +231,44 @@ pub async fn profile_pre_proxy(uuid: String, query: ProfileQuery) -> Result<(Str
     Ok((dst, uuid, queries))
 }
 
+/// Find the profile record by the name which the proxy server returns.
+///
+/// In master slave mode, names of main server profiles are not changed, so they are found by source name first.
+async fn find_by_proxy_name(name: &str) -> Result<Option<profiles::Model>, CustomError> {
+    if CONFIG.enable_master_slave_mode {
+        match find_by_backend_and_name(&CONFIG.main, name).await {
+            Ok(Some(row)) => { return Ok(Some(row)); }
+            Ok(None) => {}
+            Err(err) => { return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))); }
+        }
+    }
+    match find_by_name(name).await {
+        Ok(res) => { Ok(res) }
+        Err(err) => { Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))) }
+    }
+}
+
+/// Find the backend server and source UUID of the profile to get its legacy skin.
+pub async fn legacy_skin_pre_proxy(name: &str) -> Result<(String, String), CustomError> {
+    match find_by_proxy_name(name).await? {
+        Some(row) => { Ok((row.backend_id, row.src_uuid)) }
+        None => { Err(CustomError::HttpException(StatusCode::NOT_FOUND, "No such skin".to_string())) }
+    }
+}
+
+/// Find the backend server and source name of the profile to look it up by name.
+///
+/// In master slave mode, unknown names are looked up on the main server, as profiles of it are not renamed.
+pub async fn profile_by_name_pre_proxy(name: &str) -> Result<(String, String), CustomError> {
+    match find_by_proxy_name(name).await? {
+        Some(row) => { Ok((row.backend_id, row.src_name)) }
+        None if CONFIG.enable_master_slave_mode => { Ok((CONFIG.main.clone(), name.to_string())) }
+        None => {
+            Err(CustomError::HttpException(StatusCode::NOT_FOUND, format!("Couldn't find any profile with name {}", name)))
+        }
+    }
+}
+
 pub async fn profiles_pre_proxy(request: Vec<String>) -> Result<HashMap<String, Vec<String>>, CustomError> {
     let mut futures = vec![];
     let mut ret = HashMap::new();
@@ -171,10 +279,8 @@ pub async fn profiles_pre_proxy(request: Vec<String>) -> Result<HashMap<String,
     };
     let results = join_all(futures).await;
     let mut c_results = vec![];
-    for res in results {
-        if let Ok((name, r)) = res {
-            if let Ok(row) = r { c_results.push((name, row)) }
-        }
+    for (name, r) in results.into_iter().flatten() {
+        if let Ok(row) = r { c_results.push((name, row)) }
     }
     for (name, res) in c_results {
         // TODO: Logic bug
@@ -201,10 +307,24 @@ pub async fn profiles_pre_proxy(request: Vec<String>) -> Result<HashMap<String,
 }
 
 pub async fn validate_pre_proxy(request: ValidateRequest) -> Result<HashMap<String, ValidateRequest>, CustomError> {
-    let claims = match decode_token(&request.access_token) {
+    let claims = match decode_token(&request.access_token).await {
         Ok(claims) => { claims }
         Err(err) => { return Err(err); }
     };
+    check_client_token(&claims, &request.client_token)?;
+    Ok(backend_requests(claims))
+}
+
+/// Both valid and temporarily invalid tokens can be invalidated, and the client token is ignored.
+pub async fn invalidate_pre_proxy(request: ValidateRequest) -> Result<HashMap<String, ValidateRequest>, CustomError> {
+    let claims = match decode_refreshable_token(&request.access_token).await {
+        Ok(claims) => { claims }
+        Err(err) => { return Err(err); }
+    };
+    Ok(backend_requests(claims))
+}
+
+fn backend_requests(claims: AccessClaims) -> HashMap<String, ValidateRequest> {
     let mut ret = HashMap::new();
     for (dst, token) in claims.tokens {
         let request = ValidateRequest {
@@ -213,5 +333,5 @@ pub async fn validate_pre_proxy(request: ValidateRequest) -> Result<HashMap<Stri
         };
         ret.insert(dst, request);
     };
-    Ok(ret)
+    ret
 }
\ No newline at end of file
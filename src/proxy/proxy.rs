@@ -1,16 +1,17 @@
 use std::collections::HashMap;
-use chrono::{DateTime, Duration, Local};
 use futures::future::join_all;
 use reqwest::StatusCode;
-use sea_orm::ActiveValue;
 use crate::model::{AccessClaims, Profile, User};
 use crate::model::errors::CustomError;
 use crate::model::reply::{AuthenticateReply, RefreshReply};
 use crate::proxy::translate;
-use crate::repository::{find_by_backend_and_uuid, save_profile};
-use crate::utils::create_token;
+use crate::repository::find_by_backend_and_uuid;
+use crate::utils::{client_token_hash, create_token};
 
-pub async fn authenticate_proxy(reply: HashMap<String, AuthenticateReply>) -> Result<AuthenticateReply, CustomError> {
+/// The access token will be bound to the client token, which has been sent to all backend servers.
+///
+/// Replies are in the order of backend servers, so that profiles are always listed in the same order.
+pub async fn authenticate_proxy(username: &str, client_token: String, reply: Vec<(String, AuthenticateReply)>) -> Result<AuthenticateReply, CustomError> {
     if reply.is_empty() {
         return Err(CustomError::ForbiddenOperationException
             (StatusCode::FORBIDDEN, "Invalid credentials. Invalid username or password.".to_string()));
@@ -21,7 +22,6 @@ pub async fn authenticate_proxy(reply: HashMap<String, AuthenticateReply>) -> Re
     let mut selected: HashMap<String, bool> = HashMap::new();
     let mut access_token: HashMap<String, String> = HashMap::new();
     let mut uuids: HashMap<String, String> = HashMap::new();
-    let mut client_token = None;
 
     for (id, reply) in reply {
         for profile in reply.available_profiles {
@@ -35,7 +35,7 @@ pub async fn authenticate_proxy(reply: HashMap<String, AuthenticateReply>) -> Re
         }
         // it is not important
         if let Some(u) = reply.user {
-            if let None = user { user = Some(u) }
+            if user.is_none() { user = Some(u) }
         }
 
         // never set selected profile in authenticate reply, because user may need to choose another backend server.
@@ -46,22 +46,25 @@ pub async fn authenticate_proxy(reply: HashMap<String, AuthenticateReply>) -> Re
             Some(_) => { selected.insert(id.clone(), true); }
         }
         access_token.insert(id.clone(), reply.access_token);
-        client_token = reply.client_token;
     }
 
-    let now: DateTime<Local> = Local::now();
-    let exp: DateTime<Local> = now + Duration::days(7);
     let access_claims = AccessClaims {
+        exp: 0, // it will be set when creating the token
+        soft_exp: None,
+        auth_time: None,
+        sub: Some(username.to_string()),
+        jti: None,
+        iat: None,
+        client_token: Some(client_token_hash(&client_token)),
         tokens: access_token,
         uuids,
         selected,
         selected_uuid: None,
-        exp: exp.timestamp_millis(),
     };
 
     Ok(AuthenticateReply {
-        access_token: create_token(&access_claims),
-        client_token,
+        access_token: create_token(&access_claims).await?,
+        client_token: Some(client_token),
         available_profiles: profiles,
         selected_profile: None,
         user,
@@ -71,27 +74,12 @@ pub async fn authenticate_proxy(reply: HashMap<String, AuthenticateReply>) -> Re
 pub async fn refresh_proxy(backend: String, mut access_claims: AccessClaims, reply: RefreshReply) -> Result<RefreshReply, CustomError> {
     let selected_profile;
     if let Some(profile) = reply.selected_profile {
-        // update profile src_name and name
-        let row = match find_by_backend_and_uuid(&backend, &profile.id).await {
-            Ok(res) => {
-                match res {
-                    None => { return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, "No such profile".to_string())); }
-                    Some(r) => { r }
-                }
-            }
+        // the profile should have been recorded when authenticating,
+        // and its src_name and name will be updated by translating
+        match find_by_backend_and_uuid(&backend, &profile.id).await {
+            Ok(None) => { return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, "No such profile".to_string())); }
+            Ok(Some(_)) => {}
             Err(err) => { return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))); }
-        };
-        let name = profile.name.clone();
-        let model = crate::entity::profiles::ActiveModel {
-            id: ActiveValue::Set(row.id),
-            backend_id: ActiveValue::Set(row.backend_id),
-            src_name: ActiveValue::Set(name.clone()),
-            src_uuid: ActiveValue::Set(row.src_uuid),
-            uuid: ActiveValue::Set(row.uuid),
-            name: ActiveValue::Set(format!("{}_{}", &backend, name)),
-        };
-        if let Err(err) = save_profile(model).await {
-            return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err)));
         }
 
         match translate(&backend, profile).await {
@@ -111,11 +99,8 @@ pub async fn refresh_proxy(backend: String, mut access_claims: AccessClaims, rep
     }
 
     access_claims.tokens.insert(backend, reply.access_token);
-    let now: DateTime<Local> = Local::now();
-    let exp: DateTime<Local> = now + Duration::days(7);
-    access_claims.exp = exp.timestamp_millis();
     Ok(RefreshReply {
-        access_token: create_token(&access_claims),
+        access_token: create_token(&access_claims).await?,
         client_token: reply.client_token,
         selected_profile,
         user: reply.user,
@@ -143,10 +128,8 @@ pub async fn profiles_proxy(reply: HashMap<String, Vec<Profile>>) -> Vec<Profile
     };
     let results = join_all(futures).await;
     let mut ret = vec![];
-    for res in results {
-        if let Ok(p) = res {
-            ret.extend(p);
-        }
+    for p in results.into_iter().flatten() {
+        ret.extend(p);
     }
     ret
 }
\ No newline at end of file
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+use chrono::Utc;
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use crate::CONFIG;
+use crate::model::errors::CustomError;
+use crate::model::reply::BackendStatusReply;
+use crate::proxy::client;
+
+/// Health state of a backend server
+struct Health {
+    up: bool,
+    /// Consecutive failures of probes and requests
+    failures: u32,
+    checked_at: Option<i64>,
+    error: Option<String>,
+}
+
+/// A backend server, requests to it are sent by the shared http client with its own request timeout.
+///
+/// If its connect timeout is configured, it has a dedicated http client (and connection pool) instead.
+///
+/// A backend server is marked down after failing several times in a row,
+/// and requests to it will be rejected at once until a probe succeeds.
+pub struct Backend {
+    pub id: String,
+    url: String,
+    client: Client,
+    request_timeout: Duration,
+    health: RwLock<Health>,
+}
+
+/// Enabled backend servers in the configured order
+static REGISTRY: OnceCell<Vec<Backend>> = OnceCell::new();
+
+/// Build the enabled backend servers and their http clients, so that configuration errors are found before serving.
+///
+/// It must be called after the shared http client is initialized.
+pub fn init() -> Result<(), String> {
+    let mut backends = vec![];
+    for config in CONFIG.enabled_backends() {
+        let options = &config.options;
+        let client = match options.connect_timeout {
+            Some(timeout) => {
+                client::build(timeout).map_err(|err| format!("Build http client of <{}> failed: {}", config.id, err))?
+            }
+            None => { client::shared().clone() }
+        };
+        backends.push(Backend {
+            id: config.id.clone(),
+            url: config.url.clone(),
+            client,
+            request_timeout: Duration::from_secs(options.request_timeout),
+            health: RwLock::new(Health { up: true, failures: 0, checked_at: None, error: None }),
+        });
+    }
+    REGISTRY.set(backends).map_err(|_| "Backend servers have been initialized".to_string())
+}
+
+fn backends() -> &'static [Backend] {
+    REGISTRY.get().expect("Backend servers are not initialized")
+}
+
+impl Backend {
+    /// Url of the api path on the backend server
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.url, path)
+    }
+
+    pub fn get(&self, path: &str) -> RequestBuilder {
+        self.client.get(self.url(path)).timeout(self.request_timeout)
+    }
+
+    pub fn post(&self, path: &str) -> RequestBuilder {
+        self.client.post(self.url(path)).timeout(self.request_timeout)
+    }
+
+    pub fn is_up(&self) -> bool {
+        match self.health.read() {
+            Ok(health) => { health.up }
+            Err(err) => { err.into_inner().up }
+        }
+    }
+
+    /// Send the request, and record whether the backend server is reachable and works well.
+    ///
+    /// Responses with server errors are counted as failures, but they are still returned to the caller.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, reqwest::Error> {
+        let res = request.send().await;
+        match &res {
+            Ok(resp) if resp.status().is_server_error() => {
+                self.report(Some(format!("Unexpected status {}", resp.status())))
+            }
+            Ok(_) => { self.report(None) }
+            Err(err) => { self.report(Some(err.to_string())) }
+        }
+        res
+    }
+
+    fn report(&self, error: Option<String>) {
+        let mut health = match self.health.write() {
+            Ok(health) => { health }
+            Err(err) => { err.into_inner() }
+        };
+        match error {
+            None => {
+                if !health.up { info!("Backend server <{}> is up", self.id); }
+                health.up = true;
+                health.failures = 0;
+                health.error = None;
+            }
+            Some(err) => {
+                health.failures += 1;
+                if health.up && health.failures >= CONFIG.health_check.failure_threshold {
+                    warn!("Backend server <{}> is down: {}", self.id, err);
+                    health.up = false;
+                }
+                health.error = Some(err);
+            }
+        }
+    }
+
+    /// Probe the backend server by getting its metadata
+    async fn probe(&self) {
+        let res = match self.get("").send().await {
+            Ok(resp) if resp.status() == StatusCode::OK => { None }
+            Ok(resp) => { Some(format!("Unexpected status {}", resp.status())) }
+            Err(err) => { Some(err.to_string()) }
+        };
+        self.report(res);
+        match self.health.write() {
+            Ok(mut health) => { health.checked_at = Some(Utc::now().timestamp()); }
+            Err(err) => { err.into_inner().checked_at = Some(Utc::now().timestamp()); }
+        }
+    }
+
+    fn status(&self) -> BackendStatusReply {
+        let health = match self.health.read() {
+            Ok(health) => { health }
+            Err(err) => { err.into_inner() }
+        };
+        BackendStatusReply {
+            name: CONFIG.backend(&self.id).and_then(|config| config.name.clone()),
+            up: health.up,
+            failures: health.failures,
+            checked_at: health.checked_at,
+            error: health.error.clone(),
+        }
+    }
+}
+
+/// Get the backend server to send a request to, which must be enabled and up.
+pub fn get(id: &str) -> Result<&'static Backend, CustomError> {
+    let backend = match backends().iter().find(|backend| backend.id == id) {
+        Some(backend) => { backend }
+        None if CONFIG.backend(id).is_some() => {
+            return Err(CustomError::HttpException(StatusCode::SERVICE_UNAVAILABLE,
+                                                  format!("Backend server <{}> is disabled", id)));
+        }
+        None => { return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, "Invalid backend server".to_string())); }
+    };
+    if !backend.is_up() {
+        return Err(CustomError::HttpException(StatusCode::SERVICE_UNAVAILABLE,
+                                              format!("Backend server <{}> is unavailable", id)));
+    }
+    Ok(backend)
+}
+
+/// All backend servers which are up in the configured order, requests are only sent to them when fanning out.
+pub fn available() -> Vec<&'static Backend> {
+    backends().iter().filter(|backend| backend.is_up()).collect()
+}
+
+/// Probe all backend servers periodically
+pub fn spawn_probing() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(Duration::from_secs(CONFIG.health_check.interval));
+        loop {
+            interval.tick().await;
+            futures::future::join_all(backends().iter().map(|backend| backend.probe())).await;
+        }
+    });
+}
+
+pub fn status() -> HashMap<String, BackendStatusReply> {
+    backends().iter().map(|backend| (backend.id.clone(), backend.status())).collect()
+}
@@ -0,0 +1,179 @@
+use std::path::Path;
+use std::time::Duration;
+use base64::decode;
+use log::{debug, warn};
+use reqwest::{StatusCode, Url};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use crate::CONFIG;
+use crate::model::Profile;
+use crate::model::errors::CustomError;
+use crate::proxy::{backend_meta, client};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Timeout of fetching a texture from the backend server, in seconds
+const FETCH_TIMEOUT: u64 = 10;
+
+fn cache_dir() -> &'static Path {
+    Path::new(&CONFIG.texture_proxy.cache_dir)
+}
+
+fn is_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+}
+
+/// Whether the domain matches the pattern, where a pattern starting with `.` matches all its subdomains.
+pub fn matches_domain(pattern: &str, domain: &str) -> bool {
+    let (pattern, domain) = (pattern.to_ascii_lowercase(), domain.to_ascii_lowercase());
+    if pattern.starts_with('.') { domain.ends_with(&pattern) } else { pattern == domain }
+}
+
+/// Skin domains of the backend server, both configured and published in its metadata.
+async fn skin_domains(backend: &str) -> Vec<String> {
+    let mut skin_domains = match CONFIG.backend(backend) {
+        Some(config) => { config.skin_domains.clone() }
+        None => { vec![] }
+    };
+    if let Some(meta) = backend_meta::get(backend).await {
+        skin_domains.extend(meta.meta.skin_domains.iter().cloned());
+    }
+    skin_domains
+}
+
+/// Check the source url of a texture from the backend server, whose host must be in the skin domains of it,
+/// so that the proxy never fetches anything else.
+async fn check_source(backend: &str, url: &str) -> Result<(), String> {
+    let parsed = Url::parse(url).map_err(|err| err.to_string())?;
+    if parsed.scheme() != "http" && parsed.scheme() != "https" {
+        return Err("Unsupported scheme".to_string());
+    }
+    match parsed.host_str() {
+        Some(host) if skin_domains(backend).await.iter().any(|domain| matches_domain(domain, host)) => { Ok(()) }
+        _ => { Err(format!("Host is not in the skin domains of <{}>", backend)) }
+    }
+}
+
+/// Check and record the source url of a texture in the cache directory, and return the hash of the texture.
+///
+/// The hash of a texture is the SHA-256 of its source url,
+/// so that a backend server cannot overwrite textures of other backend servers.
+async fn save_source(backend: &str, url: &str) -> Result<String, String> {
+    check_source(backend, url).await?;
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let path = cache_dir().join(format!("{}.url", hash));
+    if !path.exists() {
+        tokio::fs::create_dir_all(cache_dir()).await.map_err(|err| err.to_string())?;
+        tokio::fs::write(&path, url).await.map_err(|err| err.to_string())?;
+    }
+    Ok(hash)
+}
+
+/// Register the source url of a texture from the backend server, and return the url of it on the texture proxy.
+pub async fn register(backend: &str, url: &str) -> Result<String, String> {
+    let hash = save_source(backend, url).await?;
+    Ok(format!("{}/textures/{}", CONFIG.texture_proxy.public_url.trim_end_matches('/'), hash))
+}
+
+/// Url of the skin in the textures property of the profile
+pub fn skin_url(profile: &Profile) -> Option<String> {
+    let property = profile.properties.as_ref()?.iter().find(|property| property.name == "textures")?;
+    let payload = serde_json::from_slice::<serde_json::Value>(&decode(&property.value).ok()?).ok()?;
+    payload.get("textures")?.get("SKIN")?.get("url")?.as_str().map(String::from)
+}
+
+/// Get the texture from the backend server by its source url.
+///
+/// It is cached in the same way as the texture proxy while the texture proxy is enabled,
+/// otherwise it is fetched every time.
+pub async fn get_by_url(backend: &str, url: &str) -> Result<Vec<u8>, CustomError> {
+    let res = if CONFIG.texture_proxy.enabled {
+        save_source(backend, url).await.map(Some)
+    } else {
+        check_source(backend, url).await.map(|_| None)
+    };
+    match res {
+        Ok(Some(hash)) => { get(&hash).await }
+        Ok(None) => {
+            fetch(url).await.map_err(|err| {
+                warn!("Fetch texture {} failed: {}", url, err);
+                CustomError::HttpException(StatusCode::BAD_GATEWAY, "Fetch texture failed".to_string())
+            })
+        }
+        Err(err) => {
+            warn!("Invalid texture url {}: {}", url, err);
+            Err(CustomError::HttpException(StatusCode::BAD_GATEWAY, "Fetch texture failed".to_string()))
+        }
+    }
+}
+
+/// Fetch the texture, which must be a PNG image no larger than the max size.
+async fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    let max_size = CONFIG.texture_proxy.max_size;
+    let mut resp = client::shared().get(url)
+        .timeout(Duration::from_secs(FETCH_TIMEOUT))
+        .send().await
+        .map_err(|err| err.to_string())?;
+    if resp.status() != StatusCode::OK {
+        return Err(format!("Unexpected status {}", resp.status()));
+    }
+    let mut texture = vec![];
+    while let Some(chunk) = resp.chunk().await.map_err(|err| err.to_string())? {
+        texture.extend_from_slice(&chunk);
+        if texture.len() as u64 > max_size {
+            return Err(format!("Texture is larger than {} bytes", max_size));
+        }
+    }
+    if !texture.starts_with(PNG_SIGNATURE) {
+        return Err("Texture is not a PNG image".to_string());
+    }
+    Ok(texture)
+}
+
+/// Get the texture from the disk cache, or fetch it from its source url and cache it.
+pub async fn get(hash: &str) -> Result<Vec<u8>, CustomError> {
+    if !is_hash(hash) {
+        return Err(CustomError::HttpException(StatusCode::NOT_FOUND, "No such texture".to_string()));
+    }
+    let path = cache_dir().join(format!("{}.png", hash));
+    if let Ok(texture) = tokio::fs::read(&path).await {
+        return Ok(texture);
+    }
+    let url = match tokio::fs::read_to_string(cache_dir().join(format!("{}.url", hash))).await {
+        Ok(url) => { url }
+        Err(_) => { return Err(CustomError::HttpException(StatusCode::NOT_FOUND, "No such texture".to_string())); }
+    };
+    let texture = match fetch(&url).await {
+        Ok(texture) => { texture }
+        Err(err) => {
+            warn!("Fetch texture {} failed: {}", url, err);
+            return Err(CustomError::HttpException(StatusCode::BAD_GATEWAY, "Fetch texture failed".to_string()));
+        }
+    };
+    debug!("Cache texture {} as {}", url, hash);
+    // write to a temporary file first, so that a partially written texture is never served
+    let tmp = cache_dir().join(format!("{}.{}.tmp", hash, Uuid::new_v4().simple()));
+    let res = match tokio::fs::write(&tmp, &texture).await {
+        Ok(_) => { tokio::fs::rename(&tmp, &path).await }
+        Err(err) => { Err(err) }
+    };
+    if let Err(err) = res {
+        warn!("Cache texture {} failed: {}", hash, err);
+        let _ = tokio::fs::remove_file(&tmp).await;
+    }
+    Ok(texture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_domains() {
+        assert!(matches_domain("littleskin.cn", "littleskin.cn"));
+        assert!(matches_domain("littleskin.cn", "LittleSkin.cn"));
+        assert!(!matches_domain("littleskin.cn", "cdn.littleskin.cn"));
+        assert!(matches_domain(".example.com", "textures.example.com"));
+        assert!(!matches_domain(".example.com", "example.com"));
+        assert!(!matches_domain(".example.com", "evil-example.com"));
+    }
+}
@@ -0,0 +1,132 @@
+use log::warn;
+use md5::{Digest, Md5};
+use reqwest::StatusCode;
+use uuid::{Builder, Uuid};
+use crate::CONFIG;
+use crate::entity::profiles;
+use crate::model::UuidStrategy;
+use crate::model::errors::CustomError;
+use crate::repository::{find_by_backend_and_uuid, find_by_uuid};
+
+/// Namespace of version 5 UUIDs allocated by the proxy server.
+/// Never change it, or the UUIDs cannot be rebuilt after losing the database.
+const PROXY_NAMESPACE: Uuid = Uuid::from_u128(0x453af3282c2a4ed991655a6c59174520);
+
+fn simple(uuid: Uuid) -> String {
+    uuid.simple().to_string()
+}
+
+fn random() -> String {
+    simple(Uuid::new_v4())
+}
+
+/// Same as `UUID.nameUUIDFromBytes(("OfflinePlayer:" + name).getBytes(UTF_8))` in Java,
+/// which is what offline mode servers use.
+fn offline(name: &str) -> String {
+    let digest = Md5::digest(format!("OfflinePlayer:{}", name).as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest);
+    simple(Builder::from_md5_bytes(bytes).into_uuid())
+}
+
+fn v5(backend: &str, src_uuid: &str) -> String {
+    let src_uuid = src_uuid.replace('-', "").to_lowercase();
+    simple(Uuid::new_v5(&PROXY_NAMESPACE, format!("{}:{}", backend, src_uuid).as_bytes()))
+}
+
+/// Find the profile which has taken the UUID, either stored with the UUID,
+/// or a main server profile which is returned with its source UUID in master slave mode.
+async fn find_taken(backend: &str, uuid: &str) -> Result<Option<profiles::Model>, CustomError> {
+    if CONFIG.enable_master_slave_mode && backend != CONFIG.main {
+        match find_by_backend_and_uuid(&CONFIG.main, uuid).await {
+            Ok(None) => {}
+            Ok(Some(row)) => { return Ok(Some(row)); }
+            Err(err) => { return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))); }
+        }
+    }
+    match find_by_uuid(uuid).await {
+        Ok(res) => { Ok(res) }
+        Err(err) => { Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))) }
+    }
+}
+
+/// Allocate the proxy profile UUID for a new source profile by the UUID strategy of its backend server.
+///
+/// If the UUID produced by the strategy has been taken by another profile,
+/// a random version 4 UUID will be used instead.
+pub async fn allocate(backend: &str, src_uuid: &str, name: &str) -> Result<String, CustomError> {
+    let strategy = CONFIG.options_of(backend).uuid;
+    let uuid = match strategy {
+        UuidStrategy::Random => { random() }
+        UuidStrategy::Source => { src_uuid.replace('-', "").to_lowercase() }
+        UuidStrategy::V5 => { v5(backend, src_uuid) }
+        UuidStrategy::Offline => { offline(name) }
+    };
+    match find_taken(backend, &uuid).await? {
+        None => { Ok(uuid) }
+        Some(row) => {
+            warn!("UUID {} of profile <{}> {} has been taken by <{}> {}, use a random one instead",
+                uuid, backend, src_uuid, row.backend_id, row.src_uuid);
+            Ok(random())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::ActiveValue::{NotSet, Set};
+    use crate::repository::{init_memory, save_profile};
+    use super::*;
+
+    /// Profiles are kept in the memory store shared by all tests, so each test uses its own UUIDs.
+    async fn save(backend_id: &str, src_uuid: &str, uuid: &str, name: &str) {
+        let profile = profiles::ActiveModel {
+            id: NotSet,
+            backend_id: Set(backend_id.to_string()),
+            src_name: Set(name.to_string()),
+            src_uuid: Set(src_uuid.to_string()),
+            uuid: Set(uuid.to_string()),
+            name: Set(name.to_string()),
+        };
+        save_profile(profile).await.unwrap();
+    }
+
+    #[test]
+    fn offline_uuid_matches_java() {
+        assert_eq!(offline("Notch"), "b50ad385829d3141a2167e7d7539ba7f");
+    }
+
+    #[test]
+    fn v5_uuid_is_stable() {
+        let uuid = v5("b", "069a79f4-44e9-4726-a5be-fca90e38aaf5");
+        assert_eq!(uuid, v5("b", "069A79F444E94726A5BEFCA90E38AAF5"));
+        assert_ne!(uuid, v5("c", "069a79f444e94726a5befca90e38aaf5"));
+        assert_eq!(Uuid::parse_str(&uuid).unwrap().get_version_num(), 5);
+    }
+
+    #[tokio::test]
+    async fn allocate_by_strategy() {
+        init_memory();
+        let uuid = allocate("b", "00000000000000000000000000000b01", "b_UuRandom").await.unwrap();
+        assert_eq!(Uuid::parse_str(&uuid).unwrap().get_version_num(), 4);
+        let uuid = allocate("c", "00000000-0000-0000-0000-000000000C01", "UuSource").await.unwrap();
+        assert_eq!(uuid, "00000000000000000000000000000c01");
+    }
+
+    #[tokio::test]
+    async fn allocate_random_uuid_when_taken() {
+        init_memory();
+        save("b", "00000000000000000000000000000b02", "00000000000000000000000000000c02", "b_UuTaken").await;
+        let uuid = allocate("c", "00000000000000000000000000000c02", "UuTaken").await.unwrap();
+        assert_ne!(uuid, "00000000000000000000000000000c02");
+        assert_eq!(Uuid::parse_str(&uuid).unwrap().get_version_num(), 4);
+    }
+
+    #[tokio::test]
+    async fn main_source_uuids_are_taken_in_master_slave_mode() {
+        init_memory();
+        save("a", "00000000000000000000000000000c03", "00000000000000000000000000000a03", "a_UuMain").await;
+        let uuid = allocate("c", "00000000000000000000000000000c03", "UuMain").await.unwrap();
+        assert_ne!(uuid, "00000000000000000000000000000c03");
+    }
+}
@@ -0,0 +1,216 @@
+use async_trait::async_trait;
+use sea_orm::*;
+use std::time::Duration;
+use sea_orm::sea_query::{Expr, OnConflict};
+use crate::entity::prelude::{KeyPairs, Players, Profiles, Reports, Revocations, Sessions};
+use crate::entity::{key_pairs, players, profiles, reports, revocations, sessions};
+use crate::migration;
+use crate::repository::{KeyPairStore, PlayerStore, ProfileStore, ReportStore, SessionStore};
+
+/// Profile store backed by a relational database (MySql, PostgreSQL or SQLite).
+pub struct DatabaseStore {
+    db: DatabaseConnection,
+}
+
+impl DatabaseStore {
+    /// Connect to the database, and apply pending schema migrations.
+    pub async fn connect(data_source: &str) -> Result<DatabaseStore, DbErr> {
+        let mut opt = ConnectOptions::new(data_source.to_string());
+        opt.max_connections(100)
+            .min_connections(5)
+            .connect_timeout(Duration::from_secs(8))
+            .idle_timeout(Duration::from_secs(8))
+            .max_lifetime(Duration::from_secs(8))
+            .sqlx_logging(false)
+            .sqlx_logging_level(log::LevelFilter::Info);
+        let db = Database::connect(opt).await?;
+        migration::run(&db).await?;
+        Ok(DatabaseStore { db })
+    }
+}
+
+#[async_trait]
+impl ProfileStore for DatabaseStore {
+    async fn find_by_backend_and_uuid(&self, backend_id: &str, src_uuid: &str) -> Result<Option<profiles::Model>, DbErr> {
+        Profiles::find()
+            .filter(profiles::Column::BackendId.eq(backend_id))
+            .filter(profiles::Column::SrcUuid.eq(src_uuid))
+            .one(&self.db)
+            .await
+    }
+
+    async fn find_by_backend_and_name(&self, backend_id: &str, src_name: &str) -> Result<Option<profiles::Model>, DbErr> {
+        Profiles::find()
+            .filter(profiles::Column::BackendId.eq(backend_id))
+            .filter(profiles::Column::SrcName.eq(src_name))
+            .one(&self.db)
+            .await
+    }
+
+    async fn find_by_name(&self, name: &str) -> Result<Option<profiles::Model>, DbErr> {
+        Profiles::find()
+            .filter(profiles::Column::Name.eq(name))
+            .one(&self.db)
+            .await
+    }
+
+    async fn find_by_uuid(&self, uuid: &str) -> Result<Option<profiles::Model>, DbErr> {
+        Profiles::find()
+            .filter(profiles::Column::Uuid.eq(uuid))
+            .one(&self.db)
+            .await
+    }
+
+    async fn save_profile(&self, profile: profiles::ActiveModel) -> Result<profiles::ActiveModel, DbErr> {
+        profile.save(&self.db).await
+    }
+}
+
+#[async_trait]
+impl SessionStore for DatabaseStore {
+    async fn find_session(&self, id: &str) -> Result<Option<sessions::Model>, DbErr> {
+        Sessions::find_by_id(id.to_string()).one(&self.db).await
+    }
+
+    async fn list_sessions(&self) -> Result<Vec<sessions::Model>, DbErr> {
+        Sessions::find()
+            .order_by_asc(sessions::Column::CreatedAt)
+            .all(&self.db)
+            .await
+    }
+
+    async fn save_session(&self, session: sessions::Model) -> Result<(), DbErr> {
+        let session: sessions::ActiveModel = session.into();
+        Sessions::insert(session).exec(&self.db).await?;
+        Ok(())
+    }
+
+    async fn delete_session(&self, id: &str) -> Result<bool, DbErr> {
+        let res = Sessions::delete_by_id(id.to_string()).exec(&self.db).await?;
+        Ok(res.rows_affected > 0)
+    }
+
+    async fn find_sessions_by_username(&self, username: &str) -> Result<Vec<sessions::Model>, DbErr> {
+        Sessions::find()
+            .filter(sessions::Column::Username.eq(username))
+            .all(&self.db)
+            .await
+    }
+
+    async fn delete_expired_sessions(&self, now: i64) -> Result<u64, DbErr> {
+        let res = Sessions::delete_many()
+            .filter(sessions::Column::ExpiresAt.lte(now))
+            .exec(&self.db)
+            .await?;
+        Ok(res.rows_affected)
+    }
+
+    async fn find_revocation(&self, id: &str) -> Result<Option<revocations::Model>, DbErr> {
+        Revocations::find_by_id(id.to_string()).one(&self.db).await
+    }
+
+    async fn save_revocation(&self, revocation: revocations::Model) -> Result<(), DbErr> {
+        let revocation: revocations::ActiveModel = revocation.into();
+        Revocations::insert(revocation)
+            .on_conflict(
+                OnConflict::column(revocations::Column::Id)
+                    .update_columns([revocations::Column::RevokedAt, revocations::Column::ExpiresAt])
+                    .to_owned()
+            )
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_expired_revocations(&self, now: i64) -> Result<u64, DbErr> {
+        let res = Revocations::delete_many()
+            .filter(revocations::Column::ExpiresAt.lte(now))
+            .exec(&self.db)
+            .await?;
+        Ok(res.rows_affected)
+    }
+}
+
+#[async_trait]
+impl PlayerStore for DatabaseStore {
+    async fn find_player(&self, uuid: &str) -> Result<Option<players::Model>, DbErr> {
+        Players::find_by_id(uuid.to_string()).one(&self.db).await
+    }
+
+    async fn save_player(&self, player: players::Model) -> Result<(), DbErr> {
+        let player: players::ActiveModel = player.into();
+        Players::insert(player)
+            .on_conflict(
+                OnConflict::column(players::Column::Uuid)
+                    .update_columns([
+                        players::Column::OnlineChat,
+                        players::Column::MultiplayerServer,
+                        players::Column::MultiplayerRealms,
+                        players::Column::ProfanityFilter,
+                        players::Column::BlockedProfiles,
+                    ])
+                    .to_owned()
+            )
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl KeyPairStore for DatabaseStore {
+    async fn find_key_pair(&self, uuid: &str) -> Result<Option<key_pairs::Model>, DbErr> {
+        KeyPairs::find_by_id(uuid.to_string()).one(&self.db).await
+    }
+
+    async fn save_key_pair(&self, key_pair: key_pairs::Model) -> Result<(), DbErr> {
+        let key_pair: key_pairs::ActiveModel = key_pair.into();
+        KeyPairs::insert(key_pair)
+            .on_conflict(
+                OnConflict::column(key_pairs::Column::Uuid)
+                    .update_columns([
+                        key_pairs::Column::PrivateKey,
+                        key_pairs::Column::PublicKey,
+                        key_pairs::Column::ExpiresAt,
+                        key_pairs::Column::RefreshedAfter,
+                    ])
+                    .to_owned()
+            )
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_expired_key_pairs(&self, now: i64) -> Result<u64, DbErr> {
+        let res = KeyPairs::delete_many()
+            .filter(key_pairs::Column::ExpiresAt.lte(now))
+            .exec(&self.db)
+            .await?;
+        Ok(res.rows_affected)
+    }
+}
+
+#[async_trait]
+impl ReportStore for DatabaseStore {
+    async fn save_report(&self, report: reports::Model) -> Result<(), DbErr> {
+        let report: reports::ActiveModel = report.into();
+        Reports::insert(report).exec(&self.db).await?;
+        Ok(())
+    }
+
+    async fn list_reports(&self) -> Result<Vec<reports::Model>, DbErr> {
+        Reports::find()
+            .order_by_asc(reports::Column::CreatedAt)
+            .all(&self.db)
+            .await
+    }
+
+    async fn resolve_report(&self, id: &str, now: i64) -> Result<bool, DbErr> {
+        let res = Reports::update_many()
+            .col_expr(reports::Column::ResolvedAt, Expr::value(now))
+            .filter(reports::Column::Id.eq(id))
+            .exec(&self.db)
+            .await?;
+        Ok(res.rows_affected > 0)
+    }
+}
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use async_trait::async_trait;
+use sea_orm::{ActiveValue, DbErr};
+use crate::entity::{key_pairs, players, profiles, reports, revocations, sessions};
+use crate::repository::{KeyPairStore, PlayerStore, ProfileStore, ReportStore, SessionStore};
+
+/// Store which keeps all records in memory.
+///
+/// It follows the same unique constraints as the database tables,
+/// so it behaves like the database store except that nothing is persisted.
+pub struct MemoryStore {
+    profiles: RwLock<Vec<profiles::Model>>,
+    sessions: RwLock<HashMap<String, sessions::Model>>,
+    revocations: RwLock<HashMap<String, revocations::Model>>,
+    players: RwLock<HashMap<String, players::Model>>,
+    key_pairs: RwLock<HashMap<String, key_pairs::Model>>,
+    reports: RwLock<HashMap<String, reports::Model>>,
+}
+
+fn lock_err<E: ToString>(err: E) -> DbErr {
+    DbErr::Custom(err.to_string())
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore {
+            profiles: RwLock::new(vec![]),
+            sessions: RwLock::new(HashMap::new()),
+            revocations: RwLock::new(HashMap::new()),
+            players: RwLock::new(HashMap::new()),
+            key_pairs: RwLock::new(HashMap::new()),
+            reports: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn find<P>(&self, predicate: P) -> Result<Option<profiles::Model>, DbErr>
+        where P: Fn(&profiles::Model) -> bool {
+        let profiles = self.profiles.read().map_err(lock_err)?;
+        Ok(profiles.iter().find(|p| predicate(p)).cloned())
+    }
+}
+
+/// Take the value of an active value, or use the fallback one if it is not set.
+fn value_or<V: Into<sea_orm::Value>>(mut value: ActiveValue<V>, fallback: Option<V>) -> Result<V, DbErr> {
+    match value.take().or(fallback) {
+        Some(v) => { Ok(v) }
+        None => { Err(DbErr::Custom("Column value is not set".to_string())) }
+    }
+}
+
+#[async_trait]
+impl ProfileStore for MemoryStore {
+    async fn find_by_backend_and_uuid(&self, backend_id: &str, src_uuid: &str) -> Result<Option<profiles::Model>, DbErr> {
+        self.find(|p| p.backend_id == backend_id && p.src_uuid == src_uuid)
+    }
+
+    async fn find_by_backend_and_name(&self, backend_id: &str, src_name: &str) -> Result<Option<profiles::Model>, DbErr> {
+        self.find(|p| p.backend_id == backend_id && p.src_name == src_name)
+    }
+
+    async fn find_by_name(&self, name: &str) -> Result<Option<profiles::Model>, DbErr> {
+        self.find(|p| p.name == name)
+    }
+
+    async fn find_by_uuid(&self, uuid: &str) -> Result<Option<profiles::Model>, DbErr> {
+        self.find(|p| p.uuid == uuid)
+    }
+
+    async fn save_profile(&self, profile: profiles::ActiveModel) -> Result<profiles::ActiveModel, DbErr> {
+        let mut profiles = self.profiles.write().map_err(lock_err)?;
+        let mut id = profile.id;
+        let old = match id.take() {
+            None => { None }
+            Some(id) => {
+                match profiles.iter().position(|p| p.id == id) {
+                    None => { return Err(DbErr::RecordNotFound(format!("No profile with id {}", id))); }
+                    Some(index) => { Some(profiles.remove(index)) }
+                }
+            }
+        };
+        let model = profiles::Model {
+            id: match &old {
+                None => { profiles.iter().map(|p| p.id).max().unwrap_or(0) + 1 }
+                Some(o) => { o.id }
+            },
+            backend_id: value_or(profile.backend_id, old.as_ref().map(|o| o.backend_id.clone()))?,
+            src_name: value_or(profile.src_name, old.as_ref().map(|o| o.src_name.clone()))?,
+            src_uuid: value_or(profile.src_uuid, old.as_ref().map(|o| o.src_uuid.clone()))?,
+            uuid: value_or(profile.uuid, old.as_ref().map(|o| o.uuid.clone()))?,
+            name: value_or(profile.name, old.as_ref().map(|o| o.name.clone()))?,
+        };
+        let duplicated = profiles.iter().any(|p| {
+            p.uuid == model.uuid || p.name == model.name
+                || (p.backend_id == model.backend_id && p.src_name == model.src_name)
+                || (p.backend_id == model.backend_id && p.src_uuid == model.src_uuid)
+        });
+        if duplicated {
+            // put the old record back, as nothing should be changed
+            if let Some(o) = old { profiles.push(o); }
+            return Err(DbErr::Custom("Duplicate entry for profiles".to_string()));
+        }
+        profiles.push(model.clone());
+        Ok(model.into())
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemoryStore {
+    async fn find_session(&self, id: &str) -> Result<Option<sessions::Model>, DbErr> {
+        Ok(self.sessions.read().map_err(lock_err)?.get(id).cloned())
+    }
+
+    async fn list_sessions(&self) -> Result<Vec<sessions::Model>, DbErr> {
+        let mut ret: Vec<sessions::Model> = self.sessions.read().map_err(lock_err)?.values().cloned().collect();
+        ret.sort_by_key(|s| s.created_at);
+        Ok(ret)
+    }
+
+    async fn save_session(&self, session: sessions::Model) -> Result<(), DbErr> {
+        let mut sessions = self.sessions.write().map_err(lock_err)?;
+        if sessions.contains_key(&session.id) {
+            return Err(DbErr::Custom("Duplicate entry for sessions".to_string()));
+        }
+        sessions.insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn delete_session(&self, id: &str) -> Result<bool, DbErr> {
+        Ok(self.sessions.write().map_err(lock_err)?.remove(id).is_some())
+    }
+
+    async fn find_sessions_by_username(&self, username: &str) -> Result<Vec<sessions::Model>, DbErr> {
+        let sessions = self.sessions.read().map_err(lock_err)?;
+        Ok(sessions.values().filter(|s| s.username == username).cloned().collect())
+    }
+
+    async fn delete_expired_sessions(&self, now: i64) -> Result<u64, DbErr> {
+        let mut sessions = self.sessions.write().map_err(lock_err)?;
+        let count = sessions.len();
+        sessions.retain(|_, s| s.expires_at > now);
+        Ok((count - sessions.len()) as u64)
+    }
+
+    async fn find_revocation(&self, id: &str) -> Result<Option<revocations::Model>, DbErr> {
+        Ok(self.revocations.read().map_err(lock_err)?.get(id).cloned())
+    }
+
+    async fn save_revocation(&self, revocation: revocations::Model) -> Result<(), DbErr> {
+        self.revocations.write().map_err(lock_err)?.insert(revocation.id.clone(), revocation);
+        Ok(())
+    }
+
+    async fn delete_expired_revocations(&self, now: i64) -> Result<u64, DbErr> {
+        let mut revocations = self.revocations.write().map_err(lock_err)?;
+        let count = revocations.len();
+        revocations.retain(|_, r| r.expires_at > now);
+        Ok((count - revocations.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl PlayerStore for MemoryStore {
+    async fn find_player(&self, uuid: &str) -> Result<Option<players::Model>, DbErr> {
+        Ok(self.players.read().map_err(lock_err)?.get(uuid).cloned())
+    }
+
+    async fn save_player(&self, player: players::Model) -> Result<(), DbErr> {
+        self.players.write().map_err(lock_err)?.insert(player.uuid.clone(), player);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl KeyPairStore for MemoryStore {
+    async fn find_key_pair(&self, uuid: &str) -> Result<Option<key_pairs::Model>, DbErr> {
+        Ok(self.key_pairs.read().map_err(lock_err)?.get(uuid).cloned())
+    }
+
+    async fn save_key_pair(&self, key_pair: key_pairs::Model) -> Result<(), DbErr> {
+        self.key_pairs.write().map_err(lock_err)?.insert(key_pair.uuid.clone(), key_pair);
+        Ok(())
+    }
+
+    async fn delete_expired_key_pairs(&self, now: i64) -> Result<u64, DbErr> {
+        let mut key_pairs = self.key_pairs.write().map_err(lock_err)?;
+        let count = key_pairs.len();
+        key_pairs.retain(|_, k| k.expires_at > now);
+        Ok((count - key_pairs.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl ReportStore for MemoryStore {
+    async fn save_report(&self, report: reports::Model) -> Result<(), DbErr> {
+        let mut reports = self.reports.write().map_err(lock_err)?;
+        if reports.contains_key(&report.id) {
+            return Err(DbErr::Custom("Duplicate entry for reports".to_string()));
+        }
+        reports.insert(report.id.clone(), report);
+        Ok(())
+    }
+
+    async fn list_reports(&self) -> Result<Vec<reports::Model>, DbErr> {
+        let mut ret: Vec<reports::Model> = self.reports.read().map_err(lock_err)?.values().cloned().collect();
+        ret.sort_by_key(|r| r.created_at);
+        Ok(ret)
+    }
+
+    async fn resolve_report(&self, id: &str, now: i64) -> Result<bool, DbErr> {
+        match self.reports.write().map_err(lock_err)?.get_mut(id) {
+            Some(report) => {
+                report.resolved_at = Some(now);
+                Ok(true)
+            }
+            None => { Ok(false) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::ActiveValue::{NotSet, Set};
+    use super::*;
+
+    fn new_profile(backend_id: &str, src_uuid: &str, src_name: &str, uuid: &str, name: &str) -> profiles::ActiveModel {
+        profiles::ActiveModel {
+            id: NotSet,
+            backend_id: Set(backend_id.to_string()),
+            src_name: Set(src_name.to_string()),
+            src_uuid: Set(src_uuid.to_string()),
+            uuid: Set(uuid.to_string()),
+            name: Set(name.to_string()),
+        }
+    }
+
+    fn session(id: &str, username: &str, expires_at: i64) -> sessions::Model {
+        sessions::Model {
+            id: id.to_string(),
+            username: username.to_string(),
+            claims: "{}".to_string(),
+            created_at: expires_at - 100,
+            expires_at,
+        }
+    }
+
+    fn report(id: &str, created_at: i64) -> reports::Model {
+        reports::Model {
+            id: id.to_string(),
+            reporter_uuid: "reporter".to_string(),
+            reported_uuid: "reported".to_string(),
+            reason: "HATE_SPEECH".to_string(),
+            comments: String::new(),
+            evidence: "[]".to_string(),
+            created_at,
+            resolved_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn save_and_find_profiles() {
+        let store = MemoryStore::new();
+        store.save_profile(new_profile("a", "src1", "Alice", "uuid1", "a_Alice")).await.unwrap();
+        store.save_profile(new_profile("b", "src1", "Bob", "uuid2", "b_Bob")).await.unwrap();
+        let row = store.find_by_backend_and_uuid("b", "src1").await.unwrap().unwrap();
+        assert_eq!(row.uuid, "uuid2");
+        assert_eq!(row.id, 2);
+        assert_eq!(store.find_by_backend_and_name("a", "Alice").await.unwrap().unwrap().uuid, "uuid1");
+        assert_eq!(store.find_by_backend_and_name("b", "Bob").await.unwrap().unwrap().uuid, "uuid2");
+        assert!(store.find_by_backend_and_name("a", "Bob").await.unwrap().is_none());
+        assert_eq!(store.find_by_name("b_Bob").await.unwrap().unwrap().backend_id, "b");
+        assert_eq!(store.find_by_uuid("uuid1").await.unwrap().unwrap().name, "a_Alice");
+        assert!(store.find_by_name("Alice").await.unwrap().is_none());
+        assert!(store.find_by_backend_and_uuid("c", "src1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn reject_duplicate_profiles() {
+        let store = MemoryStore::new();
+        store.save_profile(new_profile("a", "src1", "Alice", "uuid1", "a_Alice")).await.unwrap();
+        let duplicates = [
+            new_profile("b", "src2", "Bob", "uuid1", "b_Bob"),
+            new_profile("b", "src2", "Bob", "uuid2", "a_Alice"),
+            new_profile("a", "src2", "Alice", "uuid2", "a_Alice2"),
+            new_profile("a", "src1", "Bob", "uuid2", "a_Bob"),
+        ];
+        for profile in duplicates {
+            assert!(store.save_profile(profile).await.is_err());
+        }
+        assert_eq!(store.profiles.read().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn update_profile_by_id() {
+        let store = MemoryStore::new();
+        store.save_profile(new_profile("a", "src1", "Alice", "uuid1", "a_Alice")).await.unwrap();
+        store.save_profile(new_profile("a", "src2", "Bob", "uuid2", "a_Bob")).await.unwrap();
+        let mut profile: profiles::ActiveModel = store.find_by_uuid("uuid1").await.unwrap().unwrap().into();
+        profile.src_name = Set("Alice2".to_string());
+        store.save_profile(profile.clone()).await.unwrap();
+        let row = store.find_by_uuid("uuid1").await.unwrap().unwrap();
+        assert_eq!(row.src_name, "Alice2");
+        assert_eq!(row.name, "a_Alice");
+        // a failed update keeps the old record
+        profile.name = Set("a_Bob".to_string());
+        assert!(store.save_profile(profile).await.is_err());
+        assert_eq!(store.find_by_uuid("uuid1").await.unwrap().unwrap().name, "a_Alice");
+        let missing = profiles::ActiveModel { id: Set(42), ..new_profile("a", "src3", "Carol", "uuid3", "a_Carol") };
+        assert!(matches!(store.save_profile(missing).await, Err(DbErr::RecordNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn save_find_and_delete_sessions() {
+        let store = MemoryStore::new();
+        store.save_session(session("s1", "alice", 100)).await.unwrap();
+        store.save_session(session("s2", "alice", 200)).await.unwrap();
+        store.save_session(session("s3", "bob", 300)).await.unwrap();
+        assert!(store.save_session(session("s1", "bob", 300)).await.is_err());
+        assert_eq!(store.find_session("s1").await.unwrap().unwrap().username, "alice");
+        let ids: Vec<String> = store.list_sessions().await.unwrap().into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec!["s1", "s2", "s3"]);
+        assert!(store.delete_session("s3").await.unwrap());
+        assert!(!store.delete_session("s3").await.unwrap());
+        assert_eq!(store.delete_expired_sessions(100).await.unwrap(), 1);
+        assert!(store.find_session("s1").await.unwrap().is_none());
+        store.save_session(session("s4", "bob", 400)).await.unwrap();
+        let ids: Vec<String> = store.find_sessions_by_username("alice").await.unwrap().into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec!["s2"]);
+        assert_eq!(store.find_sessions_by_username("bob").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn save_and_prune_revocations() {
+        let store = MemoryStore::new();
+        let revocation = |id: &str, revoked_at: i64, expires_at: i64| revocations::Model {
+            id: id.to_string(),
+            revoked_at,
+            expires_at,
+        };
+        store.save_revocation(revocation("jti:1", 10_000, 100)).await.unwrap();
+        store.save_revocation(revocation("user:a:alice", 10_000, 100)).await.unwrap();
+        // signing out again replaces the old revocation
+        store.save_revocation(revocation("user:a:alice", 20_000, 200)).await.unwrap();
+        assert_eq!(store.find_revocation("user:a:alice").await.unwrap().unwrap().revoked_at, 20_000);
+        assert_eq!(store.delete_expired_revocations(150).await.unwrap(), 1);
+        assert!(store.find_revocation("jti:1").await.unwrap().is_none());
+        assert!(store.find_revocation("user:a:alice").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn save_and_replace_players() {
+        let store = MemoryStore::new();
+        let mut player = players::Model {
+            uuid: "uuid1".to_string(),
+            online_chat: true,
+            multiplayer_server: true,
+            multiplayer_realms: true,
+            profanity_filter: false,
+            blocked_profiles: "[]".to_string(),
+        };
+        assert!(store.find_player("uuid1").await.unwrap().is_none());
+        store.save_player(player.clone()).await.unwrap();
+        player.profanity_filter = true;
+        store.save_player(player).await.unwrap();
+        assert!(store.find_player("uuid1").await.unwrap().unwrap().profanity_filter);
+    }
+
+    #[tokio::test]
+    async fn save_and_prune_key_pairs() {
+        let store = MemoryStore::new();
+        let key_pair = |uuid: &str, expires_at: i64| key_pairs::Model {
+            uuid: uuid.to_string(),
+            private_key: "private".to_string(),
+            public_key: "public".to_string(),
+            expires_at,
+            refreshed_after: expires_at - 10,
+        };
+        store.save_key_pair(key_pair("uuid1", 1000)).await.unwrap();
+        store.save_key_pair(key_pair("uuid2", 3000)).await.unwrap();
+        store.save_key_pair(key_pair("uuid2", 2000)).await.unwrap();
+        assert_eq!(store.find_key_pair("uuid2").await.unwrap().unwrap().expires_at, 2000);
+        assert_eq!(store.delete_expired_key_pairs(1000).await.unwrap(), 1);
+        assert!(store.find_key_pair("uuid1").await.unwrap().is_none());
+        assert!(store.find_key_pair("uuid2").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn save_list_and_resolve_reports() {
+        let store = MemoryStore::new();
+        store.save_report(report("r2", 20)).await.unwrap();
+        store.save_report(report("r1", 10)).await.unwrap();
+        assert!(store.save_report(report("r1", 30)).await.is_err());
+        let ids: Vec<String> = store.list_reports().await.unwrap().into_iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec!["r1", "r2"]);
+        assert!(store.resolve_report("r1", 40).await.unwrap());
+        assert!(!store.resolve_report("r3", 40).await.unwrap());
+        assert_eq!(store.list_reports().await.unwrap()[0].resolved_at, Some(40));
+    }
+}
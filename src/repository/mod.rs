@@ -0,0 +1,232 @@
+mod database;
+mod memory;
+
+use async_trait::async_trait;
+use log::info;
+use once_cell::race::OnceBox;
+use sea_orm::DbErr;
+use crate::entity::{key_pairs, players, profiles, reports, revocations, sessions};
+use crate::repository::database::DatabaseStore;
+use crate::repository::memory::MemoryStore;
+
+/// Storage of the mapping between backend server profiles and proxy server profiles.
+#[async_trait]
+pub trait ProfileStore: Send + Sync {
+    async fn find_by_backend_and_uuid(&self, backend_id: &str, src_uuid: &str) -> Result<Option<profiles::Model>, DbErr>;
+
+    async fn find_by_backend_and_name(&self, backend_id: &str, src_name: &str) -> Result<Option<profiles::Model>, DbErr>;
+
+    async fn find_by_name(&self, name: &str) -> Result<Option<profiles::Model>, DbErr>;
+
+    async fn find_by_uuid(&self, uuid: &str) -> Result<Option<profiles::Model>, DbErr>;
+
+    /// Insert the profile if its id is not set, otherwise update it.
+    async fn save_profile(&self, profile: profiles::ActiveModel) -> Result<profiles::ActiveModel, DbErr>;
+}
+
+/// Storage of proxy server sessions and revoked access tokens.
+///
+/// Sessions are used when access tokens are opaque, while revocations are used for JWT access tokens.
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn find_session(&self, id: &str) -> Result<Option<sessions::Model>, DbErr>;
+
+    async fn list_sessions(&self) -> Result<Vec<sessions::Model>, DbErr>;
+
+    async fn save_session(&self, session: sessions::Model) -> Result<(), DbErr>;
+
+    /// Delete the session, and return whether it existed.
+    async fn delete_session(&self, id: &str) -> Result<bool, DbErr>;
+
+    /// Find all sessions of the user, no matter which backend servers they are issued by.
+    async fn find_sessions_by_username(&self, username: &str) -> Result<Vec<sessions::Model>, DbErr>;
+
+    /// Delete all sessions expired before the time (in seconds), and return how many sessions are deleted.
+    async fn delete_expired_sessions(&self, now: i64) -> Result<u64, DbErr>;
+
+    async fn find_revocation(&self, id: &str) -> Result<Option<revocations::Model>, DbErr>;
+
+    /// Insert the revocation, or replace the existing one with the same id.
+    async fn save_revocation(&self, revocation: revocations::Model) -> Result<(), DbErr>;
+
+    /// Delete all revocations expired before the time (in seconds), and return how many revocations are deleted.
+    async fn delete_expired_revocations(&self, now: i64) -> Result<u64, DbErr>;
+}
+
+/// Storage of player settings of proxy server profiles, such as privileges and blocklists.
+#[async_trait]
+pub trait PlayerStore: Send + Sync {
+    async fn find_player(&self, uuid: &str) -> Result<Option<players::Model>, DbErr>;
+
+    /// Insert the player, or replace the existing one with the same UUID.
+    async fn save_player(&self, player: players::Model) -> Result<(), DbErr>;
+}
+
+/// Storage of chat signing key pairs of proxy server profiles
+#[async_trait]
+pub trait KeyPairStore: Send + Sync {
+    async fn find_key_pair(&self, uuid: &str) -> Result<Option<key_pairs::Model>, DbErr>;
+
+    /// Insert the chat signing key pair, or replace the existing one of the same profile.
+    async fn save_key_pair(&self, key_pair: key_pairs::Model) -> Result<(), DbErr>;
+
+    /// Delete all key pairs expired before the time (in milliseconds), and return how many key pairs are deleted.
+    async fn delete_expired_key_pairs(&self, now: i64) -> Result<u64, DbErr>;
+}
+
+/// Storage of chat reports submitted by players
+#[async_trait]
+pub trait ReportStore: Send + Sync {
+    async fn save_report(&self, report: reports::Model) -> Result<(), DbErr>;
+
+    /// List reports in the order they are submitted.
+    async fn list_reports(&self) -> Result<Vec<reports::Model>, DbErr>;
+
+    /// Mark the report as resolved at the time, and return whether the report exists.
+    async fn resolve_report(&self, id: &str, now: i64) -> Result<bool, DbErr>;
+}
+
+pub trait Store: ProfileStore + SessionStore + PlayerStore + KeyPairStore + ReportStore {}
+
+impl<T: ProfileStore + SessionStore + PlayerStore + KeyPairStore + ReportStore> Store for T {}
+
+static STORE: OnceBox<Box<dyn Store>> = OnceBox::new();
+
+/// Initialize the store selected by the scheme of data source url.
+///
+/// - `mysql://`, `postgres://` and `sqlite://` are served by the database store.
+/// - `memory://` keeps all data in memory, and it will be lost after the proxy exits.
+///
+/// Database schema migrations will be applied before the store is ready.
+pub async fn init(data_source: &str) -> Result<(), DbErr> {
+    let store: Box<dyn Store> = if is_database(data_source)? {
+        Box::new(DatabaseStore::connect(data_source).await?)
+    } else {
+        Box::new(MemoryStore::new())
+    };
+    if STORE.set(Box::new(store)).is_err() {
+        return Err(DbErr::Custom("Store has been initialized".to_string()));
+    }
+    Ok(())
+}
+
+/// Initialize the memory store if no store has been initialized, which is shared by all tests.
+#[cfg(test)]
+pub fn init_memory() {
+    STORE.get_or_init(|| Box::new(Box::new(MemoryStore::new())));
+}
+
+/// Apply pending schema migrations to the database without starting the proxy.
+pub async fn migrate(data_source: &str) -> Result<(), DbErr> {
+    if is_database(data_source)? {
+        DatabaseStore::connect(data_source).await?;
+    } else {
+        info!("Memory store has no schema to migrate");
+    }
+    Ok(())
+}
+
+/// Check whether the data source is a database or the memory store.
+fn is_database(data_source: &str) -> Result<bool, DbErr> {
+    let scheme = match data_source.split_once(':') {
+        Some((scheme, _)) => { scheme }
+        None => { return Err(DbErr::Custom(format!("Invalid data source: {}", data_source))); }
+    };
+    match scheme {
+        "mysql" | "postgres" | "postgresql" | "sqlite" => { Ok(true) }
+        "memory" => { Ok(false) }
+        _ => { Err(DbErr::Custom(format!("Unsupported data source scheme: {}", scheme))) }
+    }
+}
+
+fn store() -> &'static dyn Store {
+    STORE.get().expect("Store is not initialized").as_ref()
+}
+
+pub async fn find_by_backend_and_uuid(backend_id: &str, src_uuid: &str) -> Result<Option<profiles::Model>, DbErr> {
+    store().find_by_backend_and_uuid(backend_id, src_uuid).await
+}
+
+pub async fn find_by_backend_and_name(backend_id: &str, src_name: &str) -> Result<Option<profiles::Model>, DbErr> {
+    store().find_by_backend_and_name(backend_id, src_name).await
+}
+
+pub async fn find_by_name(name: &str) -> Result<Option<profiles::Model>, DbErr> {
+    store().find_by_name(name).await
+}
+
+pub async fn find_by_uuid(uuid: &str) -> Result<Option<profiles::Model>, DbErr> {
+    store().find_by_uuid(uuid).await
+}
+
+pub async fn save_profile(profile: profiles::ActiveModel) -> Result<profiles::ActiveModel, DbErr> {
+    store().save_profile(profile).await
+}
+
+pub async fn find_session(id: &str) -> Result<Option<sessions::Model>, DbErr> {
+    store().find_session(id).await
+}
+
+pub async fn list_sessions() -> Result<Vec<sessions::Model>, DbErr> {
+    store().list_sessions().await
+}
+
+pub async fn save_session(session: sessions::Model) -> Result<(), DbErr> {
+    store().save_session(session).await
+}
+
+pub async fn delete_session(id: &str) -> Result<bool, DbErr> {
+    store().delete_session(id).await
+}
+
+pub async fn find_sessions_by_username(username: &str) -> Result<Vec<sessions::Model>, DbErr> {
+    store().find_sessions_by_username(username).await
+}
+
+pub async fn delete_expired_sessions(now: i64) -> Result<u64, DbErr> {
+    store().delete_expired_sessions(now).await
+}
+
+pub async fn find_revocation(id: &str) -> Result<Option<revocations::Model>, DbErr> {
+    store().find_revocation(id).await
+}
+
+pub async fn save_revocation(revocation: revocations::Model) -> Result<(), DbErr> {
+    store().save_revocation(revocation).await
+}
+
+pub async fn delete_expired_revocations(now: i64) -> Result<u64, DbErr> {
+    store().delete_expired_revocations(now).await
+}
+
+pub async fn find_player(uuid: &str) -> Result<Option<players::Model>, DbErr> {
+    store().find_player(uuid).await
+}
+
+pub async fn save_player(player: players::Model) -> Result<(), DbErr> {
+    store().save_player(player).await
+}
+
+pub async fn find_key_pair(uuid: &str) -> Result<Option<key_pairs::Model>, DbErr> {
+    store().find_key_pair(uuid).await
+}
+
+pub async fn save_key_pair(key_pair: key_pairs::Model) -> Result<(), DbErr> {
+    store().save_key_pair(key_pair).await
+}
+
+pub async fn delete_expired_key_pairs(now: i64) -> Result<u64, DbErr> {
+    store().delete_expired_key_pairs(now).await
+}
+
+pub async fn save_report(report: reports::Model) -> Result<(), DbErr> {
+    store().save_report(report).await
+}
+
+pub async fn list_reports() -> Result<Vec<reports::Model>, DbErr> {
+    store().list_reports().await
+}
+
+pub async fn resolve_report(id: &str, now: i64) -> Result<bool, DbErr> {
+    store().resolve_report(id, now).await
+}
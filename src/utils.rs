@@ -1,44 +1,518 @@
-use base64::encode;
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
-use log::debug;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use aes_gcm::aead::{Aead, AeadCore, OsRng};
+use base64::{decode_config, encode, encode_config, URL_SAFE_NO_PAD};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, Header, Validation};
+use log::{debug, info};
+use once_cell::sync::Lazy;
 use rsa::pkcs1v15::SigningKey;
+use serde_derive::Deserialize;
 use sha1::Sha1;
+use sha2::{Digest, Sha256};
 use signature::{Signature, Signer};
+use uuid::Uuid;
 use warp::http::StatusCode;
-use crate::model::AccessClaims;
+use crate::entity::{revocations, sessions};
+use crate::model::{AccessClaims, SealedClaims, TokenMode};
 use crate::model::errors::CustomError;
-use crate::{CONFIG, PRIVATE_KEY};
-
-/// Create a new proxy server access token
-pub fn create_token(claims: &AccessClaims) -> String {
-    jsonwebtoken::encode(
-        &Header::default(),
-        claims,
-        &EncodingKey::from_secret(CONFIG.secret.as_ref()),
-    ).unwrap()
-}
-
-/// Decode access token provided by proxy server
-pub fn decode_token(token: &str) -> Result<AccessClaims, CustomError> {
-    match jsonwebtoken::decode::<AccessClaims>(
-        token,
-        &DecodingKey::from_secret(CONFIG.secret.as_ref()),
-        &Validation::new(Algorithm::HS256)) {
-        Ok(val) => {
-            debug!("Decode token successfully: {:#?}", val.claims);
-            Ok(val.claims)
+use crate::repository::{delete_expired_key_pairs, delete_expired_revocations, delete_expired_sessions, delete_session, find_revocation, find_session, find_sessions_by_username, save_revocation, save_session};
+use crate::{CONFIG, PRIVATE_KEY, keyring};
+
+/// Length of AES-GCM nonce in bytes
+const NONCE_LENGTH: usize = 12;
+
+/// Cipher for access claims, whose key is derived from the token secret
+static TOKEN_CIPHER: Lazy<Aes256Gcm> = Lazy::new(|| {
+    let key = Sha256::new()
+        .chain_update(b"yggdrasil-proxy access claims:")
+        .chain_update(CONFIG.secret.as_bytes())
+        .finalize();
+    Aes256Gcm::new(&key)
+});
+
+/// Both formats of proxy server access token claims
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TokenClaims {
+    Sealed(SealedClaims),
+    /// Access claims issued before they are encrypted
+    Plain(Box<AccessClaims>),
+}
+
+/// Encrypt access claims, and encode it as `base64url(nonce || ciphertext)`
+fn seal(claims: &AccessClaims) -> String {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let plaintext = serde_json::to_vec(claims).unwrap();
+    let mut sealed = nonce.to_vec();
+    sealed.extend(TOKEN_CIPHER.encrypt(&nonce, plaintext.as_ref()).unwrap());
+    encode_config(sealed, URL_SAFE_NO_PAD)
+}
+
+fn unseal(sealed: &str) -> Option<AccessClaims> {
+    let sealed = decode_config(sealed, URL_SAFE_NO_PAD).ok()?;
+    if sealed.len() < NONCE_LENGTH { return None; }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+    let plaintext = TOKEN_CIPHER.decrypt(Nonce::from_slice(nonce), ciphertext).ok()?;
+    serde_json::from_slice(&plaintext).ok()
+}
+
+fn invalid_token() -> CustomError {
+    CustomError::ForbiddenOperationException(StatusCode::FORBIDDEN, "Invalid token.".to_string())
+}
+
+fn internal_error<E: std::fmt::Display>(err: E) -> CustomError {
+    CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))
+}
+
+/// Tokens issued by old versions have `exp` in milliseconds, convert it to seconds
+fn exp_seconds(exp: i64) -> i64 {
+    if exp > 100_000_000_000 { exp / 1000 } else { exp }
+}
+
+/// Hash the client token, so that it will never be stored in plain text
+pub fn client_token_hash(client_token: &str) -> String {
+    format!("{:x}", Sha256::digest(client_token.as_bytes()))
+}
+
+/// Check whether the client token matches the one which the access token is issued to.
+///
+/// It is skipped if the client does not provide its client token,
+/// or the access token was issued before client tokens are recorded.
+pub fn check_client_token(claims: &AccessClaims, client_token: &Option<String>) -> Result<(), CustomError> {
+    if let (Some(expected), Some(actual)) = (&claims.client_token, client_token) {
+        if *expected != client_token_hash(actual) {
+            debug!("Client token mismatches");
+            return Err(invalid_token());
         }
+    }
+    Ok(())
+}
+
+/// Create a new proxy server access token in the configured token mode.
+///
+/// Each token will be assigned a unique id, its issued time and expiry times by `tokenLifetime`.
+/// The authentication time is kept if the token is refreshed from another one,
+/// and the token never outlives the maximum session age since then.
+pub async fn create_token(claims: &AccessClaims) -> Result<String, CustomError> {
+    let lifetime = &CONFIG.token_lifetime;
+    let now = Utc::now().timestamp();
+    let mut claims = claims.clone();
+    claims.jti = Some(Uuid::new_v4().simple().to_string());
+    claims.iat = Some(Utc::now().timestamp_millis());
+    let auth_time = *claims.auth_time.get_or_insert(now);
+    claims.exp = (now + lifetime.max_token_age()).min(auth_time + lifetime.max_session_age);
+    claims.soft_exp = Some((now + lifetime.access_token).min(claims.exp));
+    match CONFIG.token_mode {
+        TokenMode::Jwt => { create_jwt(&claims) }
+        TokenMode::Session => { create_session(&claims).await }
+    }
+}
+
+/// Create a JWT access token signed by the current key of the keyring,
+/// the access claims will be encrypted in it
+fn create_jwt(claims: &AccessClaims) -> Result<String, CustomError> {
+    let sealed = SealedClaims {
+        exp: claims.exp,
+        sealed: seal(claims),
+    };
+    let keyring = keyring::current();
+    let key = match keyring.signing_key() {
+        Some(key) => { key }
+        None => { return Err(internal_error("No token signing key")); }
+    };
+    let mut header = Header::new(key.algorithm);
+    header.kid = Some(key.kid.clone());
+    jsonwebtoken::encode(&header, &sealed, &key.encoding).map_err(internal_error)
+}
+
+/// Sessions are stored by the hash of their opaque access tokens,
+/// so that the tokens cannot be used even if the database is leaked.
+pub fn session_id(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Create an opaque access token, and store the access claims as a session
+async fn create_session(claims: &AccessClaims) -> Result<String, CustomError> {
+    let token = Uuid::new_v4().simple().to_string();
+    let now = Utc::now();
+    let session = sessions::Model {
+        id: session_id(&token),
+        username: claims.sub.clone().unwrap_or_default(),
+        claims: serde_json::to_string(claims).unwrap(),
+        created_at: now.timestamp(),
+        expires_at: claims.exp,
+    };
+    save_session(session).await.map_err(internal_error)?;
+    Ok(token)
+}
+
+/// Decode a valid access token provided by proxy server.
+///
+/// Temporarily invalid access tokens are rejected, as they can only be refreshed.
+pub async fn decode_token(token: &str) -> Result<AccessClaims, CustomError> {
+    let claims = decode_refreshable_token(token).await?;
+    if claims.soft_exp.is_some_and(|soft_exp| soft_exp <= Utc::now().timestamp()) {
+        debug!("Token is temporarily invalid");
+        return Err(invalid_token());
+    }
+    Ok(claims)
+}
+
+/// Decode a valid or temporarily invalid access token provided by proxy server,
+/// no matter which token mode it is issued in.
+pub async fn decode_refreshable_token(token: &str) -> Result<AccessClaims, CustomError> {
+    let claims = if is_jwt(token) {
+        let claims = decode_jwt(token)?;
+        if is_revoked(&claims).await? {
+            debug!("Token has been revoked");
+            return Err(invalid_token());
+        }
+        claims
+    } else { decode_session(token).await? };
+    if exp_seconds(claims.exp) <= Utc::now().timestamp() {
+        debug!("Token is expired");
+        return Err(invalid_token());
+    }
+    debug!("Decode token successfully: {:#?}", claims);
+    Ok(claims)
+}
+
+fn is_jwt(token: &str) -> bool {
+    token.contains('.')
+}
+
+/// Decode JWT access token, and verify it by the key in its header.
+///
+/// Access token without key id is issued by older versions of the proxy, which is signed by the secret with HS256
+/// and may have plain access claims, and it will be accepted only if `acceptLegacyTokens` is enabled.
+fn decode_jwt(token: &str) -> Result<AccessClaims, CustomError> {
+    let kid = match jsonwebtoken::decode_header(token) {
+        Ok(header) => { header.kid }
+        Err(err) => {
+            debug!("Decode token header error: {}", err);
+            return Err(invalid_token());
+        }
+    };
+    let keyring = keyring::current();
+    let res = match kid {
+        Some(kid) => {
+            let key = match keyring.find(&kid) {
+                Some(key) => { key }
+                None => {
+                    debug!("Unknown token signing key: {}", kid);
+                    return Err(invalid_token());
+                }
+            };
+            jsonwebtoken::decode::<TokenClaims>(token, &key.decoding, &Validation::new(key.algorithm))
+        }
+        None => {
+            if !CONFIG.accept_legacy_tokens {
+                debug!("Legacy token is not accepted");
+                return Err(invalid_token());
+            }
+            jsonwebtoken::decode::<TokenClaims>(
+                token,
+                &DecodingKey::from_secret(CONFIG.secret.as_ref()),
+                &Validation::new(Algorithm::HS256))
+        }
+    };
+    let claims = match res {
+        Ok(val) => { val.claims }
         Err(err) => {
             debug!("Decode token error: {}", err);
-            Err(CustomError::ForbiddenOperationException(StatusCode::FORBIDDEN, "Invalid token.".to_string()))
+            return Err(invalid_token());
+        }
+    };
+    match claims {
+        TokenClaims::Sealed(sealed) => {
+            match unseal(&sealed.sealed) {
+                Some(claims) => { Ok(claims) }
+                None => {
+                    debug!("Unseal token error");
+                    Err(invalid_token())
+                }
+            }
+        }
+        // plain access claims are only issued by older versions, which have been checked above
+        TokenClaims::Plain(claims) => { Ok(*claims) }
+    }
+}
+
+async fn decode_session(token: &str) -> Result<AccessClaims, CustomError> {
+    let session = match find_session(&session_id(token)).await {
+        Ok(Some(session)) => { session }
+        Ok(None) => {
+            debug!("No such session");
+            return Err(invalid_token());
+        }
+        Err(err) => { return Err(internal_error(err)); }
+    };
+    if session.expires_at <= Utc::now().timestamp() {
+        debug!("Session is expired");
+        return Err(invalid_token());
+    }
+    serde_json::from_str(&session.claims).map_err(internal_error)
+}
+
+/// Id of the revocation of the user on a backend server
+fn user_revocation_id(backend: &str, username: &str) -> String {
+    format!("user:{}:{}", backend, username)
+}
+
+/// Check whether the JWT access token has been revoked by itself, or by signing out the user.
+///
+/// Signing out only revokes tokens issued by the backend servers which accept the credentials.
+async fn is_revoked(claims: &AccessClaims) -> Result<bool, CustomError> {
+    if let Some(jti) = &claims.jti {
+        if find_revocation(&format!("jti:{}", jti)).await.map_err(internal_error)?.is_some() {
+            return Ok(true);
         }
     }
+    if let Some(sub) = &claims.sub {
+        for backend in claims.tokens.keys() {
+            if let Some(revocation) = find_revocation(&user_revocation_id(backend, sub)).await.map_err(internal_error)? {
+                // tokens without issued time are issued before revocation was introduced,
+                // and those issued in the same millisecond as signing out are revoked as well
+                if claims.iat.is_none_or(|iat| iat <= revocation.revoked_at) {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Revoke the access token, so that it will never be accepted again.
+///
+/// Opaque access tokens are revoked by deleting their sessions,
+/// and JWT access tokens are revoked by recording their ids until they expire.
+pub async fn revoke_token(token: &str) -> Result<(), CustomError> {
+    if !is_jwt(token) {
+        delete_session(&session_id(token)).await.map_err(internal_error)?;
+        return Ok(());
+    }
+    let claims = decode_jwt(token)?;
+    if let Some(jti) = claims.jti {
+        let revocation = revocations::Model {
+            id: format!("jti:{}", jti),
+            revoked_at: Utc::now().timestamp_millis(),
+            expires_at: exp_seconds(claims.exp),
+        };
+        save_revocation(revocation).await.map_err(internal_error)?;
+    }
+    Ok(())
+}
+
+/// Revoke all access tokens issued for the username before now, which contain tokens of any of the backend servers.
+///
+/// Tokens of the same username from other backend servers are kept,
+/// as the username may belong to another person there.
+pub async fn revoke_user(backends: &[String], username: &str) -> Result<(), CustomError> {
+    let mut count = 0;
+    for session in find_sessions_by_username(username).await.map_err(internal_error)? {
+        let claims = serde_json::from_str::<AccessClaims>(&session.claims).map_err(internal_error)?;
+        if claims.tokens.keys().any(|backend| backends.contains(backend)) {
+            delete_session(&session.id).await.map_err(internal_error)?;
+            count += 1;
+        }
+    }
+    debug!("Revoke {} sessions of {} on {:?}", count, username, backends);
+    let now = Utc::now();
+    for backend in backends {
+        let revocation = revocations::Model {
+            id: user_revocation_id(backend, username),
+            revoked_at: now.timestamp_millis(),
+            expires_at: (now + Duration::seconds(CONFIG.token_lifetime.max_token_age())).timestamp(),
+        };
+        save_revocation(revocation).await.map_err(internal_error)?;
+    }
+    Ok(())
+}
+
+/// Delete expired sessions, revocations and chat signing key pairs
+pub async fn prune_expired() -> Result<(), CustomError> {
+    let now = Utc::now();
+    let sessions = delete_expired_sessions(now.timestamp()).await.map_err(internal_error)?;
+    let revocations = delete_expired_revocations(now.timestamp()).await.map_err(internal_error)?;
+    if sessions + revocations > 0 {
+        info!("Prune {} expired sessions and {} expired revocations", sessions, revocations);
+    }
+    // expiry time of key pairs is in milliseconds, as it is signed into the public key signature
+    let key_pairs = delete_expired_key_pairs(now.timestamp_millis()).await.map_err(internal_error)?;
+    if key_pairs > 0 {
+        info!("Prune {} expired chat signing key pairs", key_pairs);
+    }
+    Ok(())
 }
 
 /// Get a signature using the proxy server private key, and encode it with Base64
 pub fn signature(content: String) -> String {
+    sign_bytes(content.as_bytes())
+}
+
+/// Get a signature of binary content using the proxy server private key, and encode it with Base64
+pub fn sign_bytes(content: &[u8]) -> String {
     let private_key = &*PRIVATE_KEY;
     let signing_key = SigningKey::<Sha1>::new_with_prefix(private_key.clone());
-    let sign = signing_key.sign(content.as_bytes());
+    let sign = signing_key.sign(content);
     encode(sign.as_bytes())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Once;
+    use jsonwebtoken::EncodingKey;
+    use crate::repository::init_memory;
+    use super::*;
+
+    static INIT: Once = Once::new();
+
+    /// Initialize the memory store and the keyring, with keys generated in the test key directory.
+    fn init() {
+        INIT.call_once(|| {
+            init_memory();
+            keyring::init().expect("Initialize keyring failed");
+        });
+    }
+
+    /// Access claims of a token issued a while ago by the backend servers
+    fn claims(username: &str, backends: &[&str]) -> AccessClaims {
+        let now = Utc::now().timestamp();
+        AccessClaims {
+            exp: now + 3600,
+            soft_exp: None,
+            auth_time: Some(now - 10),
+            sub: Some(username.to_string()),
+            jti: Some(Uuid::new_v4().simple().to_string()),
+            iat: Some(Utc::now().timestamp_millis() - 1000),
+            client_token: None,
+            tokens: backends.iter().map(|b| (b.to_string(), format!("{}-token", b))).collect(),
+            uuids: HashMap::new(),
+            selected: HashMap::new(),
+            selected_uuid: None,
+        }
+    }
+
+    #[test]
+    fn seal_and_unseal_claims() {
+        let claims = claims("seal", &["a"]);
+        let sealed = seal(&claims);
+        assert!(!sealed.contains("a-token"));
+        assert_ne!(sealed, seal(&claims));
+        assert_eq!(unseal(&sealed).unwrap().tokens, claims.tokens);
+    }
+
+    #[test]
+    fn reject_tampered_sealed_claims() {
+        let mut sealed = decode_config(seal(&claims("tamper", &["a"])), URL_SAFE_NO_PAD).unwrap();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert!(unseal(&encode_config(&sealed, URL_SAFE_NO_PAD)).is_none());
+        assert!(unseal("short").is_none());
+    }
+
+    #[test]
+    fn check_client_token_hash() {
+        let mut claims = claims("client", &["a"]);
+        assert!(check_client_token(&claims, &Some("any".to_string())).is_ok());
+        claims.client_token = Some(client_token_hash("client-token"));
+        assert!(check_client_token(&claims, &Some("client-token".to_string())).is_ok());
+        assert!(check_client_token(&claims, &Some("other".to_string())).is_err());
+        assert!(check_client_token(&claims, &None).is_ok());
+    }
+
+    #[tokio::test]
+    async fn create_and_decode_token() {
+        init();
+        let token = create_token(&claims("create", &["a"])).await.unwrap();
+        assert!(is_jwt(&token));
+        let decoded = decode_token(&token).await.unwrap();
+        assert_eq!(decoded.sub.as_deref(), Some("create"));
+        assert_eq!(decoded.tokens.get("a").map(String::as_str), Some("a-token"));
+        assert!(decoded.soft_exp.unwrap() <= decoded.exp);
+    }
+
+    #[tokio::test]
+    async fn temporarily_invalid_token_can_only_be_refreshed() {
+        init();
+        let mut claims = claims("soft", &["a"]);
+        claims.soft_exp = Some(Utc::now().timestamp() - 1);
+        let token = create_jwt(&claims).unwrap();
+        assert!(decode_token(&token).await.is_err());
+        assert!(decode_refreshable_token(&token).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn decode_sealed_and_legacy_tokens() {
+        init();
+        let token = create_jwt(&claims("jwt", &["a"])).unwrap();
+        assert_eq!(decode_token(&token).await.unwrap().sub.as_deref(), Some("jwt"));
+        // access claims are not readable without the secret
+        let payload = decode_config(token.split('.').nth(1).unwrap(), URL_SAFE_NO_PAD).unwrap();
+        assert!(!String::from_utf8(payload).unwrap().contains("a-token"));
+        let legacy = jsonwebtoken::encode(
+            &Header::default(),
+            &claims("legacy", &["a"]),
+            &EncodingKey::from_secret(CONFIG.secret.as_ref())).unwrap();
+        assert_eq!(decode_token(&legacy).await.unwrap().sub.as_deref(), Some("legacy"));
+        // legacy tokens have expiry time in milliseconds
+        let mut expired = claims("legacy", &["a"]);
+        expired.exp = (Utc::now().timestamp() - 1) * 1000;
+        let expired = jsonwebtoken::encode(
+            &Header::default(),
+            &expired,
+            &EncodingKey::from_secret(CONFIG.secret.as_ref())).unwrap();
+        assert!(decode_token(&expired).await.is_err());
+        assert!(decode_token(&format!("{}x", token)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn revoke_jwt_by_id() {
+        init();
+        let token = create_jwt(&claims("revoke-jti", &["a"])).unwrap();
+        let other = create_jwt(&claims("revoke-jti", &["a"])).unwrap();
+        revoke_token(&token).await.unwrap();
+        assert!(decode_token(&token).await.is_err());
+        assert!(decode_token(&other).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn revoke_user_only_on_signed_out_backends() {
+        init();
+        let a = create_jwt(&claims("revoke-user", &["a"])).unwrap();
+        let b = create_jwt(&claims("revoke-user", &["b"])).unwrap();
+        let both = create_jwt(&claims("revoke-user", &["a", "b"])).unwrap();
+        revoke_user(&["a".to_string()], "revoke-user").await.unwrap();
+        assert!(decode_token(&a).await.is_err());
+        assert!(decode_token(&both).await.is_err());
+        assert!(decode_token(&b).await.is_ok());
+        // tokens issued after signing out are accepted
+        let mut claims = claims("revoke-user", &["a"]);
+        claims.iat = Some(Utc::now().timestamp_millis() + 1000);
+        assert!(decode_token(&create_jwt(&claims).unwrap()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn revoke_token_issued_just_before_signing_out() {
+        init();
+        let token = create_token(&claims("revoke-now", &["a"])).await.unwrap();
+        revoke_user(&["a".to_string()], "revoke-now").await.unwrap();
+        assert!(decode_token(&token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn create_and_revoke_sessions() {
+        init();
+        let token = create_session(&claims("session", &["a"])).await.unwrap();
+        let other = create_session(&claims("session", &["a"])).await.unwrap();
+        let kept = create_session(&claims("session", &["b"])).await.unwrap();
+        assert!(!is_jwt(&token));
+        assert_eq!(decode_token(&token).await.unwrap().sub.as_deref(), Some("session"));
+        revoke_token(&token).await.unwrap();
+        assert!(decode_token(&token).await.is_err());
+        assert!(decode_token(&other).await.is_ok());
+        revoke_user(&["a".to_string()], "session").await.unwrap();
+        assert!(decode_token(&other).await.is_err());
+        assert!(decode_token(&kept).await.is_ok());
+    }
+}
@@ -0,0 +1,74 @@
+use warp::{Filter, Reply, Rejection};
+use crate::web::admin::handlers;
+
+/// Check `Authorization: Bearer {adminToken}` header of admin api requests
+fn authorized() -> impl Filter<Extract=(), Error=Rejection> + Clone {
+    warp::header::optional::<String>("Authorization")
+        .and_then(handlers::authorize)
+        .untuple_one()
+}
+
+/// GET /admin/sessions
+pub fn sessions() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("admin" / "sessions")
+        .and(warp::get())
+        .and(authorized())
+        .and_then(handlers::sessions)
+}
+
+/// DELETE /admin/sessions/{id}
+pub fn kill_session() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("admin" / "sessions" / String)
+        .and(warp::delete())
+        .and(authorized())
+        .and_then(handlers::kill_session)
+}
+
+/// GET /admin/meta-cache
+pub fn meta_cache() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("admin" / "meta-cache")
+        .and(warp::get())
+        .and(authorized())
+        .and_then(handlers::meta_cache)
+}
+
+/// GET /admin/backends
+pub fn backends() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("admin" / "backends")
+        .and(warp::get())
+        .and(authorized())
+        .and_then(handlers::backends)
+}
+
+/// GET /admin/players/{uuid}
+pub fn player() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("admin" / "players" / String)
+        .and(warp::get())
+        .and(authorized())
+        .and_then(handlers::player)
+}
+
+/// PUT /admin/players/{uuid}
+pub fn update_player() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("admin" / "players" / String)
+        .and(warp::put())
+        .and(authorized())
+        .and(warp::body::json())
+        .and_then(handlers::update_player)
+}
+
+/// GET /admin/reports
+pub fn reports() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("admin" / "reports")
+        .and(warp::get())
+        .and(authorized())
+        .and_then(handlers::reports)
+}
+
+/// POST /admin/reports/{id}/resolve
+pub fn resolve_report() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("admin" / "reports" / String / "resolve")
+        .and(warp::post())
+        .and(authorized())
+        .and_then(handlers::resolve_report)
+}
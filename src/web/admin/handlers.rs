@@ -0,0 +1,156 @@
+use chrono::Utc;
+use uuid::Uuid;
+use warp::{Rejection, Reply};
+use warp::http::StatusCode;
+use crate::{CONFIG, reject};
+use crate::model::AccessClaims;
+use crate::model::errors::CustomError;
+use crate::model::reply::{ReportReply, SessionReply};
+use crate::model::request::PlayerRequest;
+use crate::proxy::{backend_meta, players, registry};
+use crate::repository::{delete_session, find_by_backend_and_uuid, find_by_uuid, list_reports, list_sessions, save_player};
+use crate::repository;
+
+/// Admin api is disabled (looks like not found) if `adminToken` is not configured.
+pub async fn authorize(authorization: Option<String>) -> Result<(), Rejection> {
+    let token = match &CONFIG.admin_token {
+        None => { return Err(warp::reject::not_found()); }
+        Some(token) => { token }
+    };
+    match authorization {
+        Some(auth) if auth.strip_prefix("Bearer ") == Some(token.as_str()) => { Ok(()) }
+        _ => { reject!(CustomError::ForbiddenOperationException(StatusCode::FORBIDDEN, "Invalid admin token.".to_string())) }
+    }
+}
+
+/// List all sessions, including the expired ones which have not been pruned.
+pub async fn sessions() -> Result<impl Reply, Rejection> {
+    let sessions = match list_sessions().await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))); }
+    };
+    let mut ret = vec![];
+    for session in sessions {
+        let claims = match serde_json::from_str::<AccessClaims>(&session.claims) {
+            Ok(res) => { res }
+            Err(err) => { return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))); }
+        };
+        ret.push(SessionReply {
+            id: session.id,
+            username: session.username,
+            profiles: claims.uuids.into_keys().collect(),
+            selected_profile: claims.selected_uuid,
+            created_at: session.created_at,
+            expires_at: session.expires_at,
+        });
+    }
+    Ok(warp::reply::with_status(warp::reply::json(&ret), StatusCode::OK))
+}
+
+pub async fn kill_session(id: String) -> Result<impl Reply, Rejection> {
+    match delete_session(&id).await {
+        Ok(true) => { Ok(warp::reply::with_status(warp::reply::reply(), StatusCode::NO_CONTENT)) }
+        Ok(false) => { reject!(CustomError::IllegalArgumentException(StatusCode::NOT_FOUND, "No such session.".to_string())) }
+        Err(err) => { reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))) }
+    }
+}
+
+/// Statistics of the backend server metadata cache
+pub async fn meta_cache() -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::with_status(warp::reply::json(&backend_meta::stats()), StatusCode::OK))
+}
+
+/// Health status of all backend servers
+pub async fn backends() -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::with_status(warp::reply::json(&registry::status()), StatusCode::OK))
+}
+
+/// Resolve the UUID which the proxy server returns for a known profile, so that settings are not saved for nobody.
+///
+/// In master slave mode, main server profiles are returned with their source UUIDs, so they are found by source UUID first.
+async fn resolve_profile(uuid: &str) -> Result<String, CustomError> {
+    let uuid = match Uuid::parse_str(uuid) {
+        Ok(res) => { res.simple().to_string() }
+        Err(_) => { return Err(CustomError::IllegalArgumentException(StatusCode::NOT_FOUND, "No such profile.".to_string())); }
+    };
+    if CONFIG.enable_master_slave_mode {
+        match find_by_backend_and_uuid(&CONFIG.main, &uuid).await {
+            Ok(Some(_)) => { return Ok(uuid); }
+            Ok(None) => {}
+            Err(err) => { return Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))); }
+        }
+    }
+    match find_by_uuid(&uuid).await {
+        // main server profiles are found by proxy UUID only if they are translated
+        Ok(Some(row)) if !CONFIG.enable_master_slave_mode || row.backend_id != CONFIG.main => { Ok(uuid) }
+        Ok(_) => { Err(CustomError::IllegalArgumentException(StatusCode::NOT_FOUND, "No such profile.".to_string())) }
+        Err(err) => { Err(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))) }
+    }
+}
+
+/// Settings of the player with the proxy UUID, including the default ones
+pub async fn player(uuid: String) -> Result<impl Reply, Rejection> {
+    let uuid = match resolve_profile(&uuid).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    let player = match players::find_or_default(Some(&uuid)).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&players::player_reply(player)), StatusCode::OK))
+}
+
+/// Update privileges or blocklist of the player with the proxy UUID
+pub async fn update_player(uuid: String, request: PlayerRequest) -> Result<impl Reply, Rejection> {
+    let uuid = match resolve_profile(&uuid).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    let mut player = match players::find_or_default(Some(&uuid)).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    if let Some(online_chat) = request.online_chat { player.online_chat = online_chat; }
+    if let Some(multiplayer_server) = request.multiplayer_server { player.multiplayer_server = multiplayer_server; }
+    if let Some(multiplayer_realms) = request.multiplayer_realms { player.multiplayer_realms = multiplayer_realms; }
+    if let Some(profanity_filter) = request.profanity_filter { player.profanity_filter = profanity_filter; }
+    if let Some(blocked_profiles) = request.blocked_profiles {
+        let blocked_profiles = match players::normalize_profiles(&blocked_profiles) {
+            Ok(res) => { res }
+            Err(err) => { return reject!(err); }
+        };
+        player.blocked_profiles = serde_json::to_string(&blocked_profiles).unwrap();
+    }
+    if let Err(err) = save_player(player.clone()).await {
+        return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err)));
+    }
+    Ok(warp::reply::with_status(warp::reply::json(&players::player_reply(player)), StatusCode::OK))
+}
+
+/// List all chat reports, including the resolved ones.
+pub async fn reports() -> Result<impl Reply, Rejection> {
+    let reports = match list_reports().await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))); }
+    };
+    let ret: Vec<ReportReply> = reports.into_iter().map(|report| ReportReply {
+        evidence: serde_json::from_str(&report.evidence).unwrap_or_default(),
+        id: report.id,
+        reporter: report.reporter_uuid,
+        reported: report.reported_uuid,
+        reason: report.reason,
+        comments: report.comments,
+        created_at: report.created_at,
+        resolved_at: report.resolved_at,
+    }).collect();
+    Ok(warp::reply::with_status(warp::reply::json(&ret), StatusCode::OK))
+}
+
+pub async fn resolve_report(id: String) -> Result<impl Reply, Rejection> {
+    match repository::resolve_report(&id, Utc::now().timestamp()).await {
+        Ok(true) => { Ok(warp::reply::with_status(warp::reply::reply(), StatusCode::NO_CONTENT)) }
+        Ok(false) => { reject!(CustomError::IllegalArgumentException(StatusCode::NOT_FOUND, "No such report.".to_string())) }
+        Err(err) => { reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))) }
+    }
+}
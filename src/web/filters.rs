@@ -74,6 +74,15 @@ pub fn profiles() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
         .and_then(handlers::profiles)
 }
 
+/// GET /api/users/profiles/minecraft/{name} and GET /users/profiles/minecraft/{name}
+pub fn profile_by_name() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("api" / "users" / "profiles" / "minecraft" / String)
+        .or(warp::path!("users" / "profiles" / "minecraft" / String))
+        .unify()
+        .and(warp::get())
+        .and_then(handlers::profile_by_name)
+}
+
 /// GET /
 pub fn meta() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
     warp::path::end()
@@ -87,4 +96,67 @@ pub fn certificates() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clon
         .and(warp::post())
         .and(warp::header::<String>("Authorization"))
         .and_then(handlers::certificates)
-}
\ No newline at end of file
+}
+
+/// GET /minecraftservices/publickeys
+pub fn publickeys() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("minecraftservices" / "publickeys")
+        .and(warp::get())
+        .and_then(handlers::publickeys)
+}
+
+/// GET /minecraftservices/player/attributes
+pub fn attributes() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("minecraftservices" / "player" / "attributes")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("Authorization"))
+        .and_then(handlers::attributes)
+}
+
+/// POST /minecraftservices/player/attributes
+pub fn update_attributes() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("minecraftservices" / "player" / "attributes")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("Authorization"))
+        .and(warp::body::json())
+        .and_then(handlers::update_attributes)
+}
+
+/// GET /minecraftservices/privileges
+pub fn privileges() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("minecraftservices" / "privileges")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("Authorization"))
+        .and_then(handlers::privileges)
+}
+
+/// GET /minecraftservices/privacy/blocklist
+pub fn blocklist() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("minecraftservices" / "privacy" / "blocklist")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("Authorization"))
+        .and_then(handlers::blocklist)
+}
+
+/// POST /minecraftservices/player/report
+pub fn report() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("minecraftservices" / "player" / "report")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("Authorization"))
+        .and(warp::body::json())
+        .and_then(handlers::report)
+}
+
+/// GET /textures/{hash}
+pub fn texture() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("textures" / String)
+        .and(warp::get())
+        .and_then(handlers::texture)
+}
+
+/// GET /skins/MinecraftSkins/{name}.png
+pub fn legacy_skin() -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::path!("skins" / "MinecraftSkins" / String)
+        .and(warp::get())
+        .and_then(handlers::legacy_skin)
+}
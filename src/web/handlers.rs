@@ -1,88 +1,107 @@
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::error::Error;
-use std::sync::{Arc};
-use chrono::{Duration, Utc};
+use chrono::{TimeZone, Utc};
 use futures::future::join_all;
 use log::{debug, warn};
-use reqwest::Client;
+use uuid::Uuid;
 use warp::{Rejection, Reply};
 use warp::http::StatusCode;
-use crate::{CONFIG, Meta, reject};
-use crate::model::{KeyPair, Profile};
+use crate::{CONFIG, Meta, SERVICES_PUBLIC_KEYS, reject};
+use crate::model::{AccessClaims, KeyPair, LoginMode, Profile};
 use crate::model::errors::CustomError;
+use crate::proxy::registry::{self, Backend};
+use crate::proxy::{backend_meta, players, textures, translate};
 use crate::proxy::proxy::{authenticate_proxy, has_join_proxy, profile_proxy, profiles_proxy, refresh_proxy};
-use crate::model::reply::{AuthenticateReply, CertificatesReply, ErrorReply, RefreshReply};
-use crate::model::request::{AuthenticateRequest, JoinQuery, JoinRequest, LogoutRequest, ProfileQuery, RefreshRequest, ValidateRequest};
-use crate::proxy::pre_proxy::{has_join_pre_proxy, validate_pre_proxy, join_pre_proxy, profile_pre_proxy, profiles_pre_proxy, refresh_pre_proxy};
-use crate::utils::{decode_token, signature};
+use crate::model::reply::{AuthenticateReply, BlocklistReply, CertificatesReply, ErrorReply, PlayerPrivilegesReply, PublicKeyReply, PublicKeysReply, RefreshReply};
+use crate::model::request::{AttributesRequest, AuthenticateRequest, ReportRequest, JoinQuery, JoinRequest, LogoutRequest, ProfileQuery, RefreshRequest, ValidateRequest};
+use crate::proxy::pre_proxy::{has_join_pre_proxy, invalidate_pre_proxy, legacy_skin_pre_proxy, login_pre_proxy, profile_by_name_pre_proxy, validate_pre_proxy, join_pre_proxy, profile_pre_proxy, profiles_pre_proxy, refresh_pre_proxy};
+use crate::entity::reports;
+use crate::repository::{save_player, save_report};
+use crate::utils::{decode_token, revoke_token, revoke_user, signature};
 use crate::web::api::{AUTHENTICATE, HAS_JOIN, INVALIDATE, JOIN, PROFILE, PROFILES, REFRESH, SIGN_OUT, VALIDATE};
 
-/// Send authenticate request to all backend servers, and ignore those unavailable replies.
+/// Send authenticate request to all available backend servers, and ignore those unavailable replies.
 ///
 /// After receiving all available access token and profiles information,
 /// save them as jwt token. Pass jwt token as new access token to client side.
 ///
 /// Of course, proxy will do the translating work for profile signature, uuid etc.
-pub async fn authenticate(request: AuthenticateRequest) -> Result<impl Reply, Rejection> {
-    let backends = &CONFIG.backends;
-
-    let client = Arc::new(Client::new());
-    let mut futures = vec![];
+///
+/// If the client does not provide its client token, a random one will be generated,
+/// so that all backend servers and the proxy server share the same client token.
+pub async fn authenticate(mut request: AuthenticateRequest) -> Result<impl Reply, Rejection> {
+    let client_token = request.client_token.get_or_insert_with(|| Uuid::new_v4().simple().to_string()).clone();
+    let backends = match login_pre_proxy(&request.username) {
+        Ok((username, backends)) => {
+            request.username = username;
+            backends
+        }
+        Err(err) => { return reject!(err); }
+    };
 
-    for (id, url) in backends {
-        let c_client = client.clone();
-        let c_request = request.clone();
-        futures.push(tokio::spawn(async move {
-            let resp = match c_client.post(format!("{}{}", url, AUTHENTICATE)).json(&c_request).send().await {
-                Ok(res) => { res }
-                Err(err) => { return Err(err); }
-            };
-            match resp.json::<AuthenticateReply>().await {
-                Ok(res) => { Ok((id.clone(), res)) }
-                Err(err) => { Err(err) }
+    let mut replies: Vec<(String, AuthenticateReply)> = vec![];
+    match CONFIG.login_mode {
+        LoginMode::Parallel => {
+            let mut futures = vec![];
+            for backend in backends {
+                let c_request = request.clone();
+                futures.push(tokio::spawn(async move {
+                    authenticate_backend(backend, &c_request).await.map(|reply| (backend.id.clone(), reply))
+                }));
             }
-        }));
-    }
-
-    let results = join_all(futures).await;
-    let mut replies: HashMap<String, AuthenticateReply> = HashMap::new();
-
-    for res in results {
-        if let Err(_) = res { continue; }
-        let i_res = res.unwrap();
-        if let Err(_) = i_res { continue; }
-        let (id, reply) = i_res.unwrap();
-        debug!("Get authenticate reply from <{}>: {:#?}", &id, &reply);
-        replies.insert(id, reply);
+            let results = join_all(futures).await;
+            for (id, reply) in results.into_iter().flatten().flatten() {
+                debug!("Get authenticate reply from <{}>: {:#?}", &id, &reply);
+                replies.push((id, reply));
+            }
+        }
+        LoginMode::Sequential => {
+            for backend in backends {
+                match authenticate_backend(backend, &request).await {
+                    Ok(reply) => {
+                        debug!("Get authenticate reply from <{}>: {:#?}", &backend.id, &reply);
+                        replies.push((backend.id.clone(), reply));
+                        break;
+                    }
+                    Err(err) => { debug!("Authenticate on <{}> failed: {}", &backend.id, err); }
+                }
+            }
+        }
     }
 
-    match authenticate_proxy(replies).await {
+    match authenticate_proxy(&request.username, client_token, replies).await {
         Ok(reply) => { Ok(warp::reply::with_status(warp::reply::json(&reply), StatusCode::OK)) }
         Err(err) => { reject!(err) }
     }
 }
 
+/// Error replies of failed logins cannot be parsed, so they are also errors.
+async fn authenticate_backend(backend: &Backend, request: &AuthenticateRequest) -> Result<AuthenticateReply, reqwest::Error> {
+    let resp = match backend.send(backend.post(AUTHENTICATE).json(request)).await {
+        Ok(res) => { res }
+        Err(err) => { return Err(err); }
+    };
+    resp.json::<AuthenticateReply>().await
+}
+
+/// The previous access token will be revoked after refreshing successfully.
 pub async fn refresh(request: RefreshRequest) -> Result<impl Reply, Rejection> {
     debug!("Source request: {:#?}", request);
-    let config = &*CONFIG;
+    let old_token = request.access_token.clone();
     let res = refresh_pre_proxy(request).await;
     if let Err(err) = res { return Err(warp::reject::custom(err)); }
     let (dst, access_claims, req) = res.unwrap();
     debug!("Real request: {:#?}", req);
-    let resp;
     // send request
-    match config.backends.get(&dst) {
-        None => {
-            return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, "Invalid destination.".to_string()));
-        }
-        Some(url) => {
-            match Client::new().post(format!("{}{}", url, REFRESH)).json(&req).send().await {
-                Ok(res) => { resp = res }
-                Err(err) => { return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))); }
-            }
-        }
-    }
+    let backend = match registry::get(&dst) {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    let resp = match backend.send(backend.post(REFRESH).json(&req)).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))); }
+    };
 
     let resp = resp.text().await.unwrap();
     debug!("Source reply: {:#?}", resp);
@@ -91,6 +110,7 @@ pub async fn refresh(request: RefreshRequest) -> Result<impl Reply, Rejection> {
             match refresh_proxy(dst, access_claims, reply).await {
                 Ok(reply) => {
                     debug!("Real reply: {:#?}", reply);
+                    if let Err(err) = revoke_token(&old_token).await { return reject!(err); }
                     Ok(warp::reply::with_status(warp::reply::json(&reply), StatusCode::OK))
                 }
                 Err(err) => { reject!(err) }
@@ -108,26 +128,35 @@ pub async fn refresh(request: RefreshRequest) -> Result<impl Reply, Rejection> {
     }
 }
 
+/// The token is valid if any of its backend server tokens is valid.
+///
+/// Unavailable backend servers are skipped, and it fails if none of them is available.
 pub async fn validate(request: ValidateRequest) -> Result<impl Reply, Rejection> {
     let request = match validate_pre_proxy(request).await {
         Ok(res) => { res }
         Err(err) => { return reject!(err); }
     };
     let mut futures = vec![];
+    let mut unavailable = None;
     for (dst, req) in request {
+        let backend = match registry::get(&dst) {
+            Ok(res) => { res }
+            Err(err) => {
+                unavailable = Some(err);
+                continue;
+            }
+        };
         futures.push(tokio::spawn(async move {
-            let url = CONFIG.backends.get(&dst).unwrap();
-            Client::new().post(format!("{}{}", url, VALIDATE)).json(&req).send().await
+            backend.send(backend.post(VALIDATE).json(&req)).await
         }))
     }
+    if futures.is_empty() {
+        if let Some(err) = unavailable { return reject!(err); }
+    }
     let results = join_all(futures).await;
     let mut ok = false;
-    for res in results {
-        if let Ok(r) = res {
-            if let Ok(r) = r {
-                if r.status() == StatusCode::NO_CONTENT { ok = true }
-            }
-        }
+    for r in results.into_iter().flatten().flatten() {
+        if r.status() == StatusCode::NO_CONTENT { ok = true }
     }
     if ok {
         Ok(warp::reply::with_status(warp::reply::reply(), StatusCode::NO_CONTENT))
@@ -136,48 +165,68 @@ pub async fn validate(request: ValidateRequest) -> Result<impl Reply, Rejection>
     }
 }
 
+/// Invalidate the tokens of all backend servers, and revoke the proxy access token.
 pub async fn invalidate(request: ValidateRequest) -> Result<impl Reply, Rejection> {
-    let request = match validate_pre_proxy(request).await {
+    let token = request.access_token.clone();
+    let request = match invalidate_pre_proxy(request).await {
         Ok(res) => { res }
         Err(err) => { return reject!(err); }
     };
+    if let Err(err) = revoke_token(&token).await { return reject!(err); }
     for (dst, req) in request {
-        tokio::spawn(async move {
-            let url = CONFIG.backends.get(&dst).unwrap();
-            Client::new().post(format!("{}{}", url, INVALIDATE)).json(&req).send().await
-        });
+        if let Ok(backend) = registry::get(&dst) {
+            tokio::spawn(async move {
+                backend.send(backend.post(INVALIDATE).json(&req)).await
+            });
+        }
     }
     Ok(warp::reply::with_status(warp::reply::reply(), StatusCode::NO_CONTENT))
 }
 
-/// Send sign out request to all backend servers and ignore replies
-pub async fn logout(request: LogoutRequest) -> Result<impl Reply, Rejection> {
-    let config = &*CONFIG;
-    let backends = &config.backends;
-    let client = Arc::new(Client::new());
+/// Send sign out request to all available backend servers.
+///
+/// Proxy access tokens of the user will be revoked if they contain tokens of the backend servers which accept the credentials.
+pub async fn logout(mut request: LogoutRequest) -> Result<impl Reply, Rejection> {
+    let backends = match login_pre_proxy(&request.username) {
+        Ok((username, backends)) => {
+            request.username = username;
+            backends
+        }
+        Err(err) => { return reject!(err); }
+    };
+    let mut futures = vec![];
 
-    for url in backends.values() {
-        let c_client = client.clone();
+    for backend in backends {
         let c_request = request.clone();
-        tokio::spawn(async move {
-            let _ = c_client.post(format!("{}{}", url, SIGN_OUT)).json(&c_request).send().await.map_err(|err| {
+        futures.push(tokio::spawn(async move {
+            let resp = backend.send(backend.post(SIGN_OUT).json(&c_request)).await.map_err(|err| {
                 warn!("{}", err)
             });
-        });
+            (backend.id.clone(), resp)
+        }));
+    }
+    let results = join_all(futures).await;
+    let signed_out: Vec<String> = results.into_iter().flatten()
+        .filter(|(_, resp)| matches!(resp, Ok(r) if r.status() == StatusCode::NO_CONTENT))
+        .map(|(id, _)| id)
+        .collect();
+    if !signed_out.is_empty() {
+        if let Err(err) = revoke_user(&signed_out, &request.username).await { return reject!(err); }
     }
     Ok(warp::reply::with_status(warp::reply::reply(), StatusCode::NO_CONTENT))
 }
 
 pub async fn join(request: JoinRequest) -> Result<impl Reply, Rejection> {
-    let config = &*CONFIG;
-    let resp;
-    match join_pre_proxy(request).await {
+    let resp = match join_pre_proxy(request).await {
         Ok((dst, req)) => {
-            let url = config.backends.get(&dst).unwrap();
-            resp = Client::new().post(format!("{}{}", url, JOIN)).json(&req).send().await;
+            let backend = match registry::get(&dst) {
+                Ok(res) => { res }
+                Err(err) => { return reject!(err); }
+            };
+            backend.send(backend.post(JOIN).json(&req)).await
         }
         Err(err) => { return reject!(err); }
-    }
+    };
     let resp = match resp {
         Ok(res) => { res }
         Err(err) => { return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())); }
@@ -196,8 +245,11 @@ pub async fn has_join(query: JoinQuery) -> Result<impl Reply, Rejection> {
     let resp = match has_join_pre_proxy(query).await {
         Ok((d, queries)) => {
             dst = d;
-            let url = CONFIG.backends.get(&dst).unwrap();
-            Client::new().get(format!("{}{}", url, HAS_JOIN)).query(&queries).send().await
+            let backend = match registry::get(&dst) {
+                Ok(res) => { res }
+                Err(err) => { return reject!(err); }
+            };
+            backend.send(backend.get(HAS_JOIN).query(&queries)).await
         }
         Err(err) => { return reject!(err); }
     };
@@ -211,7 +263,7 @@ pub async fn has_join(query: JoinQuery) -> Result<impl Reply, Rejection> {
         Ok(res) => {
             match has_join_proxy(&dst, res).await {
                 Ok(ret) => { Ok(warp::reply::with_status(warp::reply::json(&ret), StatusCode::OK)) }
-                Err(err) => { return reject!(err); }
+                Err(err) => { reject!(err) }
             }
         }
         Err(_) => { Ok(warp::reply::with_status(warp::reply::json(&reply), StatusCode::NO_CONTENT)) }
@@ -223,14 +275,13 @@ pub async fn profile(uuid: String, query: ProfileQuery) -> Result<impl Reply, Re
     let resp = match profile_pre_proxy(uuid, query).await {
         Ok((d, uuid, queries)) => {
             dst = d;
-            match CONFIG.backends.get(&dst) {
-                None => { return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, "Invalid backend server".to_string())); }
-                Some(url) => {
-                    let url = format!("{}{}{}", url, PROFILE, uuid);
-                    debug!("GET request to backend server: {}", url);
-                    Client::new().get(url).query(&queries).send().await
-                }
-            }
+            let backend = match registry::get(&dst) {
+                Ok(res) => { res }
+                Err(err) => { return reject!(err); }
+            };
+            let path = format!("{}{}", PROFILE, uuid);
+            debug!("GET request to backend server: {}", backend.url(&path));
+            backend.send(backend.get(&path).query(&queries)).await
         }
         Err(err) => {
             if let CustomError::IllegalArgumentException(_, _) = err {
@@ -267,32 +318,126 @@ pub async fn profiles(request: Vec<String>) -> Result<impl Reply, Rejection> {
     };
     let mut futures = vec![];
     for (dst, request) in p_request {
+        // profiles of unavailable backend servers are just missing
+        let backend = match registry::get(&dst) {
+            Ok(res) => { res }
+            Err(_) => { continue; }
+        };
         futures.push(tokio::spawn(async move {
-            let url = CONFIG.backends.get(&dst).unwrap();
-            (dst, Client::new().post(format!("{}{}", url, PROFILES)).json(&request).send().await.unwrap())
+            (dst, backend.send(backend.post(PROFILES).json(&request)).await)
         }));
     }
     let results = join_all(futures).await;
     let mut ret = HashMap::new();
-    for res in results {
-        if let Ok((dst, reply)) = res {
-            if let Ok(profiles) = reply.json::<Vec<Profile>>().await {
-                ret.insert(dst, profiles);
+    for (dst, reply) in results.into_iter().flatten() {
+        let reply = match reply {
+            Ok(res) => { res }
+            Err(err) => {
+                warn!("{}", err);
+                continue;
             }
+        };
+        if let Ok(profiles) = reply.json::<Vec<Profile>>().await {
+            ret.insert(dst, profiles);
         }
     }
     let ret = profiles_proxy(ret).await;
     Ok(warp::reply::with_status(warp::reply::json(&ret), StatusCode::OK))
 }
 
+/// Look up a single profile by name like the Mojang api, which is used by server plugins.
+pub async fn profile_by_name(name: String) -> Result<impl Reply, Rejection> {
+    let not_found = CustomError::HttpException(StatusCode::NOT_FOUND, format!("Couldn't find any profile with name {}", name));
+    let (dst, src_name) = match profile_by_name_pre_proxy(&name).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    let backend = match registry::get(&dst) {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    let resp = match backend.send(backend.post(PROFILES).json(&vec![src_name.clone()])).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())); }
+    };
+    let profile = match resp.json::<Vec<Profile>>().await {
+        Ok(profiles) => { profiles.into_iter().find(|p| p.name.eq_ignore_ascii_case(&src_name)) }
+        Err(_) => { None }
+    };
+    let profile = match profile {
+        Some(profile) => { profile }
+        None => { return reject!(not_found); }
+    };
+    match translate(&dst, profile).await {
+        Ok(profile) => {
+            let reply = Profile { properties: None, ..profile };
+            Ok(warp::reply::with_status(warp::reply::json(&reply), StatusCode::OK))
+        }
+        Err(err) => { reject!(err) }
+    }
+}
+
+pub async fn texture(hash: String) -> Result<impl Reply, Rejection> {
+    if !CONFIG.texture_proxy.enabled {
+        return reject!(CustomError::HttpException(StatusCode::NOT_FOUND, "Texture proxy is not enabled".to_string()));
+    }
+    match textures::get(&hash).await {
+        Ok(texture) => { Ok(warp::reply::with_header(texture, "Content-Type", "image/png")) }
+        Err(err) => { reject!(err) }
+    }
+}
+
+/// Legacy skin api, the skin is fetched from the backend server of the profile,
+/// and cached like the texture proxy while it is enabled.
+pub async fn legacy_skin(file: String) -> Result<impl Reply, Rejection> {
+    if CONFIG.meta.legacy_skin_api != Some(true) {
+        return reject!(CustomError::HttpException(StatusCode::NOT_FOUND, "Legacy skin api is not enabled".to_string()));
+    }
+    let name = match file.strip_suffix(".png") {
+        Some(name) => { name }
+        None => { return reject!(CustomError::HttpException(StatusCode::NOT_FOUND, "No such skin".to_string())); }
+    };
+    let (dst, uuid) = match legacy_skin_pre_proxy(name).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    let backend = match registry::get(&dst) {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    let resp = match backend.send(backend.get(&format!("{}{}", PROFILE, uuid))).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())); }
+    };
+    let url = match resp.json::<Profile>().await {
+        Ok(profile) => { textures::skin_url(&profile) }
+        Err(_) => { None }
+    };
+    let url = match url {
+        Some(url) => { url }
+        None => { return reject!(CustomError::HttpException(StatusCode::NOT_FOUND, "No such skin".to_string())); }
+    };
+    match textures::get_by_url(&dst, &url).await {
+        Ok(texture) => { Ok(warp::reply::with_header(texture, "Content-Type", "image/png")) }
+        Err(err) => { reject!(err) }
+    }
+}
+
+/// Skin domains of backend servers are merged into the configured ones, if they are derived from backend metadata.
 pub async fn meta() -> Result<impl Reply, Rejection> {
     let config = &*CONFIG;
-    Ok(warp::reply::with_status(warp::reply::json(&Meta::from(config)), StatusCode::OK))
+    let mut meta = Meta::from(config);
+    if config.auto_skin_domains.enabled {
+        for domain in backend_meta::skin_domains() {
+            if !meta.skin_domains.contains(&domain) { meta.skin_domains.push(domain); }
+        }
+    }
+    Ok(warp::reply::with_status(warp::reply::json(&meta), StatusCode::OK))
 }
 
-/// Just create a random key pair.
-/// This behaviour maybe changed if Minecraft updates the use of key pair in the future.
-/// (Maybe implement the report system api)
+/// Chat signing key pair of the selected profile, which is persisted and renewed after `refreshedAfter`.
+///
+/// Tokens without selected profile get no key pair, since the key pair must be bound to a profile.
 pub async fn certificates(token: String) -> Result<impl Reply, Rejection> {
     match CONFIG.meta.enable_profile_key {
         Some(c) => {
@@ -306,28 +451,157 @@ pub async fn certificates(token: String) -> Result<impl Reply, Rejection> {
     }
     // Work as Mojang
     if token.len() < 7 { return Ok(warp::reply::with_status(warp::reply::json(&String::new()), StatusCode::NO_CONTENT)); }
-    if let Err(_) = decode_token(&token[7..token.len()]) {
-        return Ok(warp::reply::with_status(warp::reply::json(&String::new()), StatusCode::NO_CONTENT));
-    }
+    let uuid = match decode_token(&token[7..token.len()]).await {
+        Ok(AccessClaims { selected_uuid: Some(uuid), .. }) => { uuid }
+        _ => { return Ok(warp::reply::with_status(warp::reply::json(&String::new()), StatusCode::NO_CONTENT)); }
+    };
 
-    let key_pair = match KeyPair::new() {
+    let model = match players::key_pair(&uuid).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    let key_pair = KeyPair {
+        private_key: model.private_key,
+        public_key: model.public_key,
+    };
+    let public_key_signature = signature(model.expires_at.to_string() + &key_pair.public_key);
+    let public_key_signature_v2 = match players::public_key_signature_v2(&uuid, model.expires_at, &key_pair) {
         Ok(res) => { res }
         Err(err) => { return reject!(err); }
     };
-    let now = Utc::now();
-    let expires_at = now + Duration::hours(48);
-    let public_key_signature = signature(expires_at.timestamp_millis().to_string() + &key_pair.public_key);
+    let (expires_at, refreshed_after) = match (
+        Utc.timestamp_millis_opt(model.expires_at).single(),
+        Utc.timestamp_millis_opt(model.refreshed_after).single(),
+    ) {
+        (Some(expires_at), Some(refreshed_after)) => { (expires_at, refreshed_after) }
+        _ => {
+            return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, "Invalid key pair expiry time".to_string()));
+        }
+    };
     let ret = CertificatesReply {
         expires_at: expires_at.format("%+").to_string(),
         key_pair,
-        public_key_signature_v2: public_key_signature.clone(),
+        public_key_signature_v2,
         public_key_signature,
-        refreshed_after: (now + Duration::hours(36)).format("%+").to_string(),
+        refreshed_after: refreshed_after.format("%+").to_string(),
     };
     debug!("{:#?}", ret);
     Ok(warp::reply::with_status(warp::reply::json(&ret), StatusCode::OK))
 }
 
+/// Public keys which sign profile properties and player certificates, the same proxy key signs both of them.
+pub async fn publickeys() -> Result<impl Reply, Rejection> {
+    let keys = || SERVICES_PUBLIC_KEYS.iter()
+        .map(|public_key| PublicKeyReply { public_key: public_key.clone() })
+        .collect();
+    let ret = PublicKeysReply {
+        profile_property_keys: keys(),
+        player_certificate_keys: keys(),
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&ret), StatusCode::OK))
+}
+
+/// Proxy UUID of the selected profile of the `Authorization: Bearer {accessToken}` header
+async fn selected_uuid(authorization: Option<String>) -> Result<Option<String>, CustomError> {
+    let token = match authorization.as_deref().and_then(|auth| auth.strip_prefix("Bearer ")) {
+        Some(res) => { res }
+        None => { return Err(CustomError::ForbiddenOperationException(StatusCode::UNAUTHORIZED, "Invalid token.".to_string())); }
+    };
+    match decode_token(token).await {
+        Ok(claims) => { Ok(claims.selected_uuid) }
+        Err(_) => { Err(CustomError::ForbiddenOperationException(StatusCode::UNAUTHORIZED, "Invalid token.".to_string())) }
+    }
+}
+
+/// Privileges, profanity filter preferences and ban status of the selected profile
+pub async fn attributes(authorization: Option<String>) -> Result<impl Reply, Rejection> {
+    let uuid = match selected_uuid(authorization).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    let player = match players::find_or_default(uuid.as_deref()).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&players::attributes(&player)), StatusCode::OK))
+}
+
+/// Update profanity filter preferences of the selected profile
+pub async fn update_attributes(authorization: Option<String>, request: AttributesRequest) -> Result<impl Reply, Rejection> {
+    let uuid = match selected_uuid(authorization).await {
+        Ok(Some(res)) => { res }
+        Ok(None) => { return reject!(CustomError::IllegalArgumentException(StatusCode::BAD_REQUEST, "No profile selected.".to_string())); }
+        Err(err) => { return reject!(err); }
+    };
+    let mut player = match players::find_or_default(Some(&uuid)).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    if let Some(preferences) = request.profanity_filter_preferences {
+        player.profanity_filter = preferences.profanity_filter_on;
+        if let Err(err) = save_player(player.clone()).await {
+            return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err)));
+        }
+    }
+    Ok(warp::reply::with_status(warp::reply::json(&players::attributes(&player)), StatusCode::OK))
+}
+
+pub async fn privileges(authorization: Option<String>) -> Result<impl Reply, Rejection> {
+    let uuid = match selected_uuid(authorization).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    let player = match players::find_or_default(uuid.as_deref()).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    let ret = PlayerPrivilegesReply { privileges: players::privileges(&player) };
+    Ok(warp::reply::with_status(warp::reply::json(&ret), StatusCode::OK))
+}
+
+/// Profiles blocked by the selected profile, which are kept by proxy server
+pub async fn blocklist(authorization: Option<String>) -> Result<impl Reply, Rejection> {
+    let uuid = match selected_uuid(authorization).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    let player = match players::find_or_default(uuid.as_deref()).await {
+        Ok(res) => { res }
+        Err(err) => { return reject!(err); }
+    };
+    let ret = BlocklistReply { blocked_profiles: players::blocked_profiles(&player) };
+    Ok(warp::reply::with_status(warp::reply::json(&ret), StatusCode::OK))
+}
+
+/// Keep the chat report of the selected profile for admins, along with the signed messages as evidence.
+pub async fn report(authorization: Option<String>, request: ReportRequest) -> Result<impl Reply, Rejection> {
+    let reporter = match selected_uuid(authorization).await {
+        Ok(Some(res)) => { res }
+        Ok(None) => { return reject!(CustomError::IllegalArgumentException(StatusCode::BAD_REQUEST, "No profile selected.".to_string())); }
+        Err(err) => { return reject!(err); }
+    };
+    let report = request.report;
+    let reported = match Uuid::parse_str(&report.reported_entity.profile_id) {
+        Ok(res) => { res.simple().to_string() }
+        Err(_) => { return reject!(CustomError::IllegalArgumentException(StatusCode::BAD_REQUEST, "Invalid reported profile.".to_string())); }
+    };
+    let model = reports::Model {
+        id: Uuid::new_v4().simple().to_string(),
+        reporter_uuid: reporter,
+        reported_uuid: reported,
+        reason: report.reason.unwrap_or_default(),
+        comments: report.opinion_comments.unwrap_or_default(),
+        evidence: report.evidence.unwrap_or(serde_json::Value::Null).to_string(),
+        created_at: Utc::now().timestamp(),
+        resolved_at: None,
+    };
+    debug!("Receive report {} from {} against {}", model.id, model.reporter_uuid, model.reported_uuid);
+    if let Err(err) = save_report(model).await {
+        return reject!(CustomError::HttpException(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err)));
+    }
+    Ok(warp::reply::with_status(warp::reply::reply(), StatusCode::OK))
+}
+
 pub async fn err_handle(err: Rejection) -> Result<impl Reply, Infallible> {
     let mut reply = ErrorReply {
         error: "Unknown Error".to_string(),
@@ -349,9 +623,9 @@ pub async fn err_handle(err: Rejection) -> Result<impl Reply, Infallible> {
         }
     } else if let Some(e) = err.find::<CustomError>() {
         let (c, err, err_msg) = match e {
-            CustomError::ForbiddenOperationException(code, msg) => { (code.clone(), e.into(), msg.clone()) }
-            CustomError::IllegalArgumentException(code, msg) => { (code.clone(), e.into(), msg.clone()) }
-            CustomError::HttpException(code, msg) => { (code.clone(), e.into(), msg.clone()) }
+            CustomError::ForbiddenOperationException(code, msg) => { (*code, e.into(), msg.clone()) }
+            CustomError::IllegalArgumentException(code, msg) => { (*code, e.into(), msg.clone()) }
+            CustomError::HttpException(code, msg) => { (*code, e.into(), msg.clone()) }
         };
         code = c;
         reply.error = err;
@@ -1,5 +1,6 @@
 pub mod filters;
 pub mod handlers;
+pub mod admin;
 mod api;
 
 #[macro_export]